@@ -16,11 +16,22 @@ pub struct Cli {
     /// Sets the YAML test configuration file
     #[arg(short, long, default_value = ".")]
     pub dir: String,
+
+    /// Sets the environment, eg production or development. It defaults to production for build
+    /// and to development for serve.
+    #[arg(short, long)]
+    pub env: Option<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    Build {},
+    Build {
+        /// Checks the internal links and anchors of the generated site after building it
+        #[arg(long)]
+        check: bool,
+    },
+    /// Builds the site and reports internal links and anchors which point nowhere
+    Check {},
     Serve {
         /// Sets the YAML test configuration file
         #[arg(short, long, default_value = "2020")]
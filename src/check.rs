@@ -0,0 +1,368 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::i18n::language_config;
+use crate::{public_dir, Config, DocumentData};
+
+/// BrokenLink is an internal `href` or `src` whose target does not exist in the generated site,
+/// or whose `#fragment` matches no id on the target page.
+pub struct BrokenLink {
+    /// page is the generated html file, relative to the public directory.
+    pub page: String,
+    /// source is the document the page was generated from, when it was not copied as it is.
+    pub source: Option<String>,
+    pub url: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{} ({})", source, self.page)?,
+            None => write!(f, "{}", self.page)?,
+        }
+        write!(f, ": `{}` {}", self.url, self.reason)
+    }
+}
+
+/// Page is what the checker needs from a generated html file.
+#[derive(Default)]
+struct Page {
+    /// links are the `href` and `src` values, in the order they appear.
+    links: Vec<String>,
+    /// ids are every `id` in the page, which includes the ids of the table of contents, and the
+    /// `name` of anchors.
+    ids: HashSet<String>,
+}
+
+/// attributes parses the attributes of a tag, eg `a href="/about/" class=nav`.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    let mut rest = tag
+        .trim_start_matches(|c: char| c.is_ascii_alphanumeric())
+        .trim_end_matches('/');
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        if name_end == 0 {
+            break;
+        }
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let Some(value_start) = rest.strip_prefix('=') else {
+            attributes.push((name, String::new()));
+            continue;
+        };
+        let value_start = value_start.trim_start();
+        let (value, remaining) = match value_start.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value_start = &value_start[1..];
+                let end = value_start.find(quote).unwrap_or(value_start.len());
+                (
+                    &value_start[..end],
+                    value_start.get(end + 1..).unwrap_or(""),
+                )
+            }
+            _ => {
+                let end = value_start
+                    .find(char::is_whitespace)
+                    .unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            }
+        };
+        attributes.push((name, decode_entities(value)));
+        rest = remaining;
+    }
+    attributes
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn parse_page(html: &str) -> Page {
+    let mut page = Page::default();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + "-->".len());
+            rest = &rest[end..];
+            continue;
+        }
+        let mut quote = None;
+        let mut end = rest.len();
+        for (i, c) in rest.char_indices() {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), _) if q == c => quote = None,
+                (None, '>') => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let tag = &rest[1..end];
+        rest = rest.get(end + 1..).unwrap_or("");
+        if tag.starts_with('/') || tag.starts_with('!') {
+            continue;
+        }
+
+        let name: String = tag
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        for (attribute, value) in attributes(tag) {
+            match attribute.as_str() {
+                "href" | "src" => page.links.push(value),
+                "id" => {
+                    page.ids.insert(value);
+                }
+                "name" if name == "a" => {
+                    page.ids.insert(value);
+                }
+                _ => {}
+            }
+        }
+        // Urls in scripts and styles are not links.
+        if name == "script" || name == "style" {
+            let close = rest
+                .to_ascii_lowercase()
+                .find(&format!("</{}", name))
+                .unwrap_or(rest.len());
+            rest = &rest[close..];
+        }
+    }
+    page
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = value
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// is_external is true for urls with a scheme, eg `https:` or `mailto:`, and protocol relative
+/// urls.
+fn is_external(url: &str) -> bool {
+    if url.starts_with("//") {
+        return true;
+    }
+    let scheme_end = url.find(':');
+    let path_start = url.find(['/', '?', '#']);
+    match (scheme_end, path_start) {
+        (Some(colon), Some(slash)) => colon < slash,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+fn html_files(dir: &Path, files: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            html_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "html") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// site_path is the path a url points at, relative to the public directory, eg `blog/` for
+/// `/docs/blog/` on a site deployed under `/docs`.
+fn site_path(page: &str, path: &str, base_paths: &[String]) -> String {
+    let path = if let Some(root_relative) = path.strip_prefix('/') {
+        // Root-relative urls may include the base_path the site is deployed under.
+        let without_base = base_paths.iter().find_map(|base_path| {
+            let rest = path.strip_prefix(base_path.as_str())?;
+            (rest.is_empty() || rest.starts_with('/')).then(|| rest.trim_start_matches('/'))
+        });
+        without_base.unwrap_or(root_relative).to_string()
+    } else {
+        let dir = page.rsplit_once('/').map_or("", |(dir, _)| dir);
+        format!("{}/{}", dir, path)
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// resolve finds the generated file a site path points at, relative to the public directory.
+fn resolve(public: &Path, path: &str) -> Option<String> {
+    let target = public.join(path);
+    if target.is_file() {
+        Some(path.to_string())
+    } else if target.join("index.html").is_file() {
+        Some(
+            format!("{}/index.html", path)
+                .trim_start_matches('/')
+                .to_string(),
+        )
+    } else {
+        None
+    }
+}
+
+/// check_links parses every html file of the generated site, and returns the internal links
+/// which point at missing files or at missing ids. Documents map the generated files back to
+/// the documents they came from.
+pub fn check_links(
+    root_dir: &str,
+    config: &Config,
+    documents: &[DocumentData],
+) -> eyre::Result<Vec<BrokenLink>> {
+    let public = PathBuf::from(public_dir(root_dir));
+    let mut files = vec![];
+    html_files(&public, &mut files)?;
+
+    let mut pages = HashMap::new();
+    for file in &files {
+        let relative = file
+            .strip_prefix(&public)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        pages.insert(relative, parse_page(&fs::read_to_string(file)?));
+    }
+
+    let sources: HashMap<String, String> = documents
+        .iter()
+        .filter_map(|document| {
+            let destination = document
+                .file_destination_path
+                .strip_prefix(public.to_string_lossy().as_ref())?;
+            Some((
+                destination.trim_start_matches('/').to_string(),
+                document.source_path(root_dir),
+            ))
+        })
+        .collect();
+
+    let mut base_paths: Vec<String> = std::iter::once(config.base_path.clone())
+        .chain(
+            config
+                .languages
+                .keys()
+                .map(|lang| language_config(config, lang).base_path),
+        )
+        .filter(|base_path| !base_path.is_empty() && base_path != "/")
+        .map(|base_path| base_path.trim_end_matches('/').to_string())
+        .collect();
+    // The longest base_path is stripped first, eg `/docs/fr` before `/docs`.
+    base_paths.sort_by_key(|base_path| std::cmp::Reverse(base_path.len()));
+    base_paths.dedup();
+
+    // The search index is written after the site is built, and is not there when checking
+    // a site which was only built.
+    let search_dir = format!("{}/", config.search.output_subdir.trim_matches('/'));
+
+    let mut names: Vec<&String> = pages.keys().collect();
+    names.sort();
+    let mut broken = vec![];
+    for name in names {
+        let page = &pages[name];
+        let mut report = |url: &str, reason: String| {
+            broken.push(BrokenLink {
+                page: name.clone(),
+                source: sources.get(name).cloned(),
+                url: url.to_string(),
+                reason,
+            })
+        };
+        for url in &page.links {
+            let url = url.trim();
+            if url.is_empty() || is_external(url) {
+                continue;
+            }
+            let (path, fragment) = url.split_once('#').unwrap_or((url, ""));
+            let path = path.split('?').next().unwrap_or(path);
+
+            let target = if path.is_empty() {
+                Some(name.clone())
+            } else {
+                let path = site_path(name, &percent_decode(path), &base_paths);
+                if config.search.enabled && path.starts_with(&search_dir) {
+                    continue;
+                }
+                resolve(&public, &path)
+            };
+            let Some(target) = target else {
+                report(url, "points at a missing page or file".to_string());
+                continue;
+            };
+            if fragment.is_empty() {
+                continue;
+            }
+            if let Some(target_page) = pages.get(&target) {
+                if !target_page.ids.contains(&percent_decode(fragment)) {
+                    report(url, format!("points at a missing anchor on {}", target));
+                }
+            }
+        }
+    }
+    Ok(broken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_config;
+
+    #[test]
+    fn search_assets_are_not_reported() {
+        let root = std::env::temp_dir().join(format!("quickstatic-check-{}", std::process::id()));
+        let public = PathBuf::from(public_dir(&root.to_string_lossy()));
+        fs::create_dir_all(&public).unwrap();
+        fs::write(
+            public.join("index.html"),
+            r#"<link href="/pagefind/pagefind-ui.css" rel="stylesheet">
+<script src="/pagefind/pagefind-ui.js"></script>
+<a href="/missing/">missing</a>"#,
+        )
+        .unwrap();
+
+        let broken = check_links(&root.to_string_lossy(), &test_config(""), &[]).unwrap();
+        let disabled = test_config("search: {enabled: false}");
+        let broken_without_search = check_links(&root.to_string_lossy(), &disabled, &[]).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let urls: Vec<&str> = broken.iter().map(|link| link.url.as_str()).collect();
+        assert_eq!(urls, ["/missing/"]);
+        assert_eq!(broken_without_search.len(), 3);
+    }
+}
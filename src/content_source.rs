@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::Path;
+
+use eyre::{eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::{defaults, permalink, Config, DocumentData};
+
+/// ContentSource reads documents from somewhere other than markdown files, eg a database. Every
+/// record is a mapping of field names to values.
+pub trait ContentSource {
+    /// name identifies the source in errors, eg its file path.
+    fn name(&self) -> String;
+    fn records(&self, root_dir: &str) -> eyre::Result<Vec<Mapping>>;
+}
+
+/// SqliteSource runs a query against a local SQLite database, with a record per row. It needs
+/// quickstatic to be built with the `sqlite` feature, which is on by default.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SqliteSource {
+    /// path is the database file, relative to the site root.
+    pub path: String,
+    pub query: String,
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_value(value: rusqlite::types::ValueRef<'_>) -> Value {
+    use rusqlite::types::ValueRef;
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => Value::Null,
+        ValueRef::Integer(i) => Value::Number(i.into()),
+        ValueRef::Real(f) => Value::Number(f.into()),
+        ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+    }
+}
+
+impl ContentSource for SqliteSource {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn records(&self, root_dir: &str) -> eyre::Result<Vec<Mapping>> {
+        let connection = rusqlite::Connection::open_with_flags(
+            Path::new(root_dir).join(&self.path),
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .wrap_err(format!("unable to open sqlite database {}", self.path))?;
+        let mut statement = connection
+            .prepare(&self.query)
+            .wrap_err(format!("invalid query for sqlite database {}", self.path))?;
+        let columns: Vec<String> = statement
+            .column_names()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+
+        let mut rows = statement.query([])?;
+        let mut records = vec![];
+        while let Some(row) = rows.next()? {
+            let mut record = Mapping::new();
+            for (i, column) in columns.iter().enumerate() {
+                record.insert(column.as_str().into(), sqlite_value(row.get_ref(i)?));
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn records(&self, _root_dir: &str) -> eyre::Result<Vec<Mapping>> {
+        Err(eyre!(
+            "unable to read sqlite database {}: quickstatic was built without the sqlite feature",
+            self.path
+        ))
+    }
+}
+
+/// NdjsonSource reads a file with a json object per line.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NdjsonSource {
+    /// path is the ndjson file, relative to the site root.
+    pub path: String,
+}
+
+impl ContentSource for NdjsonSource {
+    fn name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn records(&self, root_dir: &str) -> eyre::Result<Vec<Mapping>> {
+        let contents = fs::read_to_string(Path::new(root_dir).join(&self.path))
+            .wrap_err(format!("unable to read ndjson file {}", self.path))?;
+        let mut records = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Mapping = serde_json::from_str(line).wrap_err(format!(
+                "invalid json object on line {} of {}",
+                i + 1,
+                self.path
+            ))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SourceKind {
+    Sqlite(SqliteSource),
+    Ndjson(NdjsonSource),
+}
+
+impl SourceKind {
+    fn source(&self) -> &dyn ContentSource {
+        match self {
+            SourceKind::Sqlite(source) => source,
+            SourceKind::Ndjson(source) => source,
+        }
+    }
+}
+
+fn default_body_field() -> String {
+    "body".to_string()
+}
+
+/// ContentSourceConfig is one entry of the `content_sources:` config list, eg
+///
+/// ``` yaml
+/// content_sources:
+///   - type: sqlite
+///     path: _quickstatic/content/changelog.db
+///     query: SELECT slug, title, date, notes AS body FROM releases
+///     layout: default/release.liquid
+///     permalink: /changelog/:slug/
+///   - type: ndjson
+///     path: _quickstatic/content/incidents.ndjson
+///     layout: default/incident.liquid
+///     permalink: /status/:id/
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ContentSourceConfig {
+    #[serde(flatten)]
+    pub kind: SourceKind,
+    /// layout renders the records, unless a record has a `layout` field.
+    #[serde(default)]
+    pub layout: Option<String>,
+    /// permalink is the pattern of the generated urls, unless a record has a `permalink` field.
+    /// It can use any field of the record, eg `/status/:id/`.
+    pub permalink: String,
+    /// body is the field holding the markdown body. The other fields become the frontmatter.
+    #[serde(default = "default_body_field")]
+    pub body: String,
+}
+
+/// content_source_documents creates a document for every record of the configured content
+/// sources. They are rendered like markdown documents.
+pub fn content_source_documents(
+    root_dir: &str,
+    config: &Config,
+) -> eyre::Result<Vec<DocumentData>> {
+    let mut documents = vec![];
+    for source_config in &config.content_sources {
+        let source = source_config.kind.source();
+        let records = source
+            .records(root_dir)
+            .wrap_err(format!("unable to read content source {}", source.name()))?;
+        for (i, mut record) in records.into_iter().enumerate() {
+            let body = match record.remove(source_config.body.as_str()) {
+                Some(Value::String(body)) => body,
+                Some(Value::Null) | None => String::new(),
+                Some(_) => {
+                    return Err(eyre!(
+                        "record {} of {} has a `{}` field which is not text",
+                        i + 1,
+                        source.name(),
+                        source_config.body
+                    ))
+                }
+            };
+            if let Some(layout) = &source_config.layout {
+                record
+                    .entry("layout".into())
+                    .or_insert_with(|| layout.clone().into());
+            }
+            record
+                .entry("permalink".into())
+                .or_insert_with(|| source_config.permalink.clone().into());
+
+            let source_path = source.name();
+            let frontmatter =
+                defaults::apply(&config.defaults, &source_path, Value::Mapping(record));
+            let destination =
+                permalink::destination(config, &source_path, "", &frontmatter, &frontmatter)
+                    .wrap_err(format!("record {} of {}", i + 1, source_path))?;
+
+            let mut document = DocumentData::new(
+                root_dir,
+                format!(
+                    "{}/{}.md",
+                    root_dir,
+                    destination
+                        .trim_start_matches('/')
+                        .trim_end_matches(".html")
+                ),
+                &destination,
+                body,
+                frontmatter,
+                0,
+            );
+            document.lang = config.default_language.clone();
+            document.origin = format!("record {} of {}", i + 1, source_path);
+            documents.push(document);
+        }
+    }
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_config;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "quickstatic-content-source-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn ndjson_lines_become_documents() {
+        let root = temp_dir("ndjson");
+        fs::write(
+            root.join("incidents.ndjson"),
+            "{\"id\": 1, \"title\": \"Outage\", \"body\": \"# Down\"}\n\n{\"id\": 2, \"permalink\": \"/status/latest/\"}\n",
+        )
+        .unwrap();
+        fs::write(root.join("broken.ndjson"), "{\"id\": 1}\nnot json\n").unwrap();
+        let config = test_config(
+            "content_sources: [{type: ndjson, path: incidents.ndjson, layout: default/incident.liquid, permalink: '/status/:id/'}]",
+        );
+        let root_dir = root.to_str().unwrap();
+        let documents = content_source_documents(root_dir, &config);
+        let broken = content_source_documents(
+            root_dir,
+            &test_config(
+                "content_sources: [{type: ndjson, path: broken.ndjson, permalink: '/:id/'}]",
+            ),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        let documents = documents.unwrap();
+        let permalinks: Vec<&str> = documents.iter().map(|d| d.permalink.as_str()).collect();
+        assert_eq!(permalinks, ["/status/1/", "/status/latest/"]);
+        assert_eq!(documents[0].markdown_body, "# Down");
+        assert_eq!(documents[0].frontmatter["title"], "Outage");
+        assert_eq!(
+            documents[0].frontmatter["layout"],
+            "default/incident.liquid"
+        );
+        assert_eq!(documents[0].frontmatter.get("body"), None);
+        assert_eq!(documents[1].origin, "record 2 of incidents.ndjson");
+        assert!(format!("{:?}", broken.unwrap_err())
+            .contains("invalid json object on line 2 of broken.ndjson"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_rows_become_documents() {
+        let root = temp_dir("sqlite");
+        let connection = rusqlite::Connection::open(root.join("changelog.db")).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE releases (slug TEXT, title TEXT, notes TEXT, downloads INTEGER);
+                 INSERT INTO releases VALUES ('v1', 'First', 'Hello', 3), ('v2', 'Second', NULL, 5);",
+            )
+            .unwrap();
+        drop(connection);
+        let config = test_config(
+            "content_sources: [{type: sqlite, path: changelog.db, query: 'SELECT slug, title, notes AS body, downloads FROM releases ORDER BY slug', permalink: '/changelog/:slug/'}]",
+        );
+        let documents = content_source_documents(root.to_str().unwrap(), &config);
+        fs::remove_dir_all(&root).unwrap();
+
+        let documents = documents.unwrap();
+        let permalinks: Vec<&str> = documents.iter().map(|d| d.permalink.as_str()).collect();
+        assert_eq!(permalinks, ["/changelog/v1/", "/changelog/v2/"]);
+        assert_eq!(documents[0].markdown_body, "Hello");
+        assert_eq!(documents[1].markdown_body, "");
+        assert_eq!(documents[1].frontmatter["downloads"], 5);
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::Path;
+
+use eyre::{eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::taxonomy::slugify;
+use crate::{defaults, permalink, Config, DocumentData};
+
+/// DataPages is one entry of the `data_pages:` config list. It generates a page per record of a
+/// data file, or of a list given inline, eg
+///
+/// ``` yaml
+/// data_pages:
+///   - file: _quickstatic/data/integrations.yaml
+///     layout: default/integration.liquid
+///     permalink: /integrations/:slug/
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DataPages {
+    /// file is a yaml or json file, relative to the site root, holding a list of records.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// key is the dotted path of the list within the file, when it is not the whole file.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// records lists the records inline, instead of reading them from a file.
+    #[serde(default)]
+    pub records: Option<Vec<Value>>,
+    pub layout: String,
+    /// permalink is the pattern of the generated urls. Besides the usual placeholders it can use
+    /// any field of the record, eg `/integrations/:id/`.
+    pub permalink: String,
+    /// title_field is the record field used as the page title. It defaults to `title`, then
+    /// `name`.
+    #[serde(default)]
+    pub title_field: Option<String>,
+}
+
+fn read_records(root_dir: &str, source: &DataPages) -> eyre::Result<(String, Vec<Value>)> {
+    if let Some(records) = &source.records {
+        return Ok(("the `data_pages` config".to_string(), records.clone()));
+    }
+    let file = source
+        .file
+        .as_ref()
+        .ok_or_else(|| eyre!("each `data_pages` entry needs either a `file` or `records`"))?;
+    let contents = fs::read_to_string(Path::new(root_dir).join(file))
+        .wrap_err(format!("unable to read data file {}", file))?;
+    let mut value: Value =
+        serde_yaml::from_str(&contents).wrap_err(format!("unable to parse data file {}", file))?;
+    if let Some(key) = &source.key {
+        for part in key.split('.') {
+            value = value
+                .get(part)
+                .cloned()
+                .ok_or_else(|| eyre!("data file {} has no `{}`", file, key))?;
+        }
+    }
+    match value {
+        Value::Sequence(records) => Ok((file.clone(), records)),
+        _ => Err(eyre!("expecting a list of records in data file {}", file)),
+    }
+}
+
+fn record_str<'a>(record: &'a Value, field: &str) -> Option<&'a str> {
+    record.get(field).and_then(Value::as_str)
+}
+
+/// data_pages creates a virtual document for every record of the configured data sources. The
+/// record is exposed to templates as `this.data`.
+pub fn data_pages(root_dir: &str, config: &Config) -> eyre::Result<Vec<DocumentData>> {
+    let mut pages = vec![];
+    for source in &config.data_pages {
+        let (source_name, records) = read_records(root_dir, source)?;
+        for (i, record) in records.into_iter().enumerate() {
+            let title = match &source.title_field {
+                Some(field) => record_str(&record, field),
+                None => record_str(&record, "title").or_else(|| record_str(&record, "name")),
+            };
+            let slug = record_str(&record, "slug")
+                .or(title)
+                .map(slugify)
+                .unwrap_or_else(|| (i + 1).to_string());
+
+            let mut frontmatter = Mapping::new();
+            frontmatter.insert("layout".into(), source.layout.clone().into());
+            if let Some(title) = title {
+                frontmatter.insert("title".into(), title.into());
+            }
+            frontmatter.insert("slug".into(), slug.into());
+            frontmatter.insert("permalink".into(), source.permalink.clone().into());
+
+            let source_path = source.file.clone().unwrap_or_default();
+            let frontmatter =
+                defaults::apply(&config.defaults, &source_path, Value::Mapping(frontmatter));
+            let destination =
+                permalink::destination(config, &source_path, "", &frontmatter, &record)
+                    .wrap_err(format!("record {} of {}", i + 1, source_name))?;
+
+            let mut page = DocumentData::new(
+                root_dir,
+                format!(
+                    "{}/{}.md",
+                    root_dir,
+                    destination
+                        .trim_start_matches('/')
+                        .trim_end_matches(".html")
+                ),
+                &destination,
+                String::new(),
+                frontmatter,
+                0,
+            );
+            page.data = record;
+            page.lang = config.default_language.clone();
+            page.origin = format!("record {} of {}", i + 1, source_name);
+            pages.push(page);
+        }
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_config;
+
+    #[test]
+    fn records_become_pages_at_their_permalinks() {
+        let config = test_config(
+            r#"
+data_pages:
+  - records:
+      - {name: GitHub Actions, id: 7}
+      - {title: Post, slug: custom}
+      - {id: 9}
+    layout: default/integration.liquid
+    permalink: /integrations/:slug/
+  - records: [{id: 7, name: Slack}]
+    layout: default/integration.liquid
+    permalink: /by-id/:id/
+"#,
+        );
+        let pages = data_pages("/site", &config).unwrap();
+        let permalinks: Vec<&str> = pages.iter().map(|p| p.permalink.as_str()).collect();
+        assert_eq!(
+            permalinks,
+            [
+                "/integrations/github-actions/",
+                "/integrations/custom/",
+                "/integrations/3/",
+                "/by-id/7/"
+            ]
+        );
+        assert_eq!(
+            pages[0].file_destination_path,
+            "/site/_quickstatic/public/integrations/github-actions/index.html"
+        );
+        assert_eq!(pages[0].frontmatter["title"], "GitHub Actions");
+        assert_eq!(pages[0].data["id"], 7);
+        assert_eq!(pages[2].origin, "record 3 of the `data_pages` config");
+    }
+
+    #[test]
+    fn records_are_read_from_a_key_of_a_data_file() {
+        let root =
+            std::env::temp_dir().join(format!("quickstatic-data-pages-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("team.yaml"),
+            "people:\n  staff:\n    - {name: Ada}\n    - {name: Grace}\n",
+        )
+        .unwrap();
+        let config = test_config(
+            "data_pages: [{file: team.yaml, key: people.staff, layout: default/person.liquid, permalink: '/team/:slug/'}]",
+        );
+        let root_dir = root.to_str().unwrap();
+        let pages = data_pages(root_dir, &config);
+        let missing_key = data_pages(
+            root_dir,
+            &test_config(
+                "data_pages: [{file: team.yaml, key: people.board, layout: default/person.liquid, permalink: '/team/:slug/'}]",
+            ),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        let permalinks: Vec<String> = pages.unwrap().into_iter().map(|p| p.permalink).collect();
+        assert_eq!(permalinks, ["/team/ada/", "/team/grace/"]);
+        assert_eq!(
+            missing_key.unwrap_err().to_string(),
+            "data file team.yaml has no `people.board`"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+/// FrontmatterDefault is one entry of the `defaults:` config list, eg
+///
+/// ``` yaml
+/// defaults:
+///   - glob: "blog/**/*.md"
+///     values:
+///       author: Jane
+///       toc: true
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FrontmatterDefault {
+    /// glob is matched against source paths relative to the site root.
+    pub glob: String,
+    pub values: Mapping,
+}
+
+/// merge copies the keys of `overrides` into `base`. Nested mappings are merged key by key, and
+/// anything else in `overrides` replaces the value in `base`.
+fn merge(base: &mut Mapping, overrides: Mapping) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Mapping(base_value)), Value::Mapping(value)) => merge(base_value, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// apply merges the defaults matching source_path into the frontmatter of a document. Later
+/// defaults override earlier ones, and the document's own frontmatter overrides them all.
+pub fn apply(defaults: &[FrontmatterDefault], source_path: &str, frontmatter: Value) -> Value {
+    let mut merged = Mapping::new();
+    for default in defaults
+        .iter()
+        .filter(|default| glob_match::glob_match(&default.glob, source_path))
+    {
+        merge(&mut merged, default.values.clone());
+    }
+    if merged.is_empty() {
+        return frontmatter;
+    }
+    match frontmatter {
+        Value::Mapping(own) => {
+            merge(&mut merged, own);
+            Value::Mapping(merged)
+        }
+        Value::Null => Value::Mapping(merged),
+        // Frontmatter which is not a mapping has no keys to merge with.
+        frontmatter => frontmatter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(source: &str) -> Value {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    fn defaults() -> Vec<FrontmatterDefault> {
+        serde_yaml::from_str(
+            r#"
+- glob: "**/*.md"
+  values: {author: Jane, toc: false, social: {twitter: jane}}
+- glob: "blog/**/*.md"
+  values: {toc: true, social: {mastodon: jane}}
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn later_defaults_override_earlier_ones() {
+        assert_eq!(
+            apply(&defaults(), "blog/post/index.md", Value::Null),
+            yaml("{author: Jane, toc: true, social: {twitter: jane, mastodon: jane}}")
+        );
+        assert_eq!(
+            apply(&defaults(), "about.md", Value::Null),
+            yaml("{author: Jane, toc: false, social: {twitter: jane}}")
+        );
+    }
+
+    #[test]
+    fn frontmatter_overrides_defaults() {
+        assert_eq!(
+            apply(
+                &defaults(),
+                "blog/post/index.md",
+                yaml("{author: Joe, social: {twitter: joe}}")
+            ),
+            yaml("{author: Joe, toc: true, social: {twitter: joe, mastodon: jane}}")
+        );
+    }
+
+    #[test]
+    fn unmatched_documents_keep_their_frontmatter() {
+        assert_eq!(
+            apply(&defaults(), "feed.liquid", yaml("{title: Feed}")),
+            yaml("{title: Feed}")
+        );
+        assert_eq!(apply(&defaults(), "feed.liquid", Value::Null), Value::Null);
+    }
+}
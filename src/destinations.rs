@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use eyre::eyre;
+
+use crate::DocumentData;
+
+fn describe(root_dir: &str, document: &DocumentData) -> String {
+    if document.origin.is_empty() {
+        document.source_path(root_dir)
+    } else {
+        document.origin.clone()
+    }
+}
+
+/// check_collisions fails when two documents would be written to the same file, eg a data page
+/// and a markdown file with the same permalink, rather than letting the last one rendered win.
+pub fn check_collisions(root_dir: &str, documents: &[DocumentData]) -> eyre::Result<()> {
+    let mut destinations: HashMap<&str, &DocumentData> = HashMap::new();
+    for document in documents {
+        if let Some(other) = destinations.insert(&document.file_destination_path, document) {
+            return Err(eyre!(
+                "{} and {} are both generated at {}",
+                describe(root_dir, other),
+                describe(root_dir, document),
+                document.permalink
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    #[test]
+    fn colliding_destinations_name_both_sources() {
+        let config = test_config("");
+        let about = test_document(&config, "about.md", "/about/index.html", "{}");
+        let blog = test_document(&config, "blog/index.md", "/blog/index.html", "{}");
+        assert!(check_collisions("/site", &[about.clone(), blog]).is_ok());
+
+        let mut record = test_document(&config, "about/index.md", "/about/index.html", "{}");
+        record.origin = "record 2 of team.yaml".to_string();
+        let error = check_collisions("/site", &[about, record]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "about.md and record 2 of team.yaml are both generated at /about/"
+        );
+    }
+}
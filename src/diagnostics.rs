@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// TemplateSource is the text a liquid template was parsed from, and where it came from.
+pub struct TemplateSource<'a> {
+    /// name is the path shown to the user, eg `./blog/index.md`.
+    pub name: String,
+    pub text: &'a str,
+    /// line_offset is the number of lines stripped from the top of the file before parsing,
+    /// eg the frontmatter of a markdown document.
+    pub line_offset: usize,
+}
+
+/// DiagnosticContext holds what is needed to explain a template error beyond the failing source.
+pub struct DiagnosticContext<'a> {
+    /// document is the source path of the document being rendered.
+    pub document: &'a str,
+    /// themes_dir is the directory partials are read from.
+    pub themes_dir: &'a str,
+    pub partials: &'a HashMap<String, String>,
+}
+
+/// Diagnostic is a template or content error, pointing at the line in the file that caused it.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    pub file: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub snippet: Option<String>,
+    pub details: Vec<(String, String)>,
+    /// chain is the path from the document through its layout and partials to the failing file.
+    pub chain: Vec<String>,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.message)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => writeln!(f, "  --> {}:{}:{}", self.file, line, column)?,
+            _ => writeln!(f, "  --> {}", self.file)?,
+        }
+        if let Some(snippet) = &self.snippet {
+            write!(f, "{}", snippet)?;
+        }
+        for (key, value) in &self.details {
+            // The list of everything available is long, and the suggestion covers it.
+            if key.starts_with("available ") {
+                continue;
+            }
+            writeln!(f, "   = {}: {}", key, value)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            writeln!(f, "   = did you mean `{}`?", suggestion)?;
+        }
+        if self.chain.len() > 1 {
+            writeln!(f, "   = while rendering: {}", self.chain.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// frontmatter_line_offset counts the lines before the markdown body of a document, which is
+/// everything gray_matter strips: the frontmatter block and the blank lines following it.
+pub fn frontmatter_line_offset(contents: &str) -> usize {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut body_start = 0;
+    if lines.first().map(|l| l.trim_end()) == Some("---") {
+        // gray_matter drops the opening delimiter even when the block is never closed.
+        body_start = match lines.iter().skip(1).position(|l| l.trim_end() == "---") {
+            Some(close) => close + 2,
+            None => 1,
+        };
+    }
+    while lines
+        .get(body_start)
+        .is_some_and(|l| l.trim_end().is_empty())
+    {
+        body_start += 1;
+    }
+    body_start
+}
+
+/// LiquidErrorParts is the rendered liquid error split back into its parts. Liquid keeps its
+/// error internals private, so they are recovered from its Display output.
+#[derive(Debug, Default)]
+struct LiquidErrorParts {
+    message: String,
+    position: Option<(usize, usize)>,
+    context: Vec<(String, String)>,
+    /// traces are ordered from the innermost tag outwards.
+    traces: Vec<String>,
+}
+
+fn parse_liquid_error(text: &str) -> LiquidErrorParts {
+    let mut parts = LiquidErrorParts::default();
+    let mut lines = text.lines();
+    let first = lines
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("liquid:")
+        .trim();
+
+    // Parse errors come from pest, and start with the position eg ` --> 3:29`.
+    if let Some(position) = first.strip_prefix("-->") {
+        if let Some((line, column)) = position.trim().split_once(':') {
+            parts.position = line.parse().ok().zip(column.parse().ok());
+        }
+    } else {
+        parts.message = first.to_string();
+    }
+
+    let mut in_context = false;
+    for line in lines {
+        if let Some(trace) = line.strip_prefix("from: ") {
+            parts.traces.push(trace.trim().to_string());
+            in_context = false;
+        } else if line.trim() == "with:" {
+            in_context = true;
+        } else if in_context {
+            if let Some((key, value)) = line.trim().split_once('=') {
+                parts.context.push((key.to_string(), value.to_string()));
+            }
+        } else if let Some(message) = line.trim().strip_prefix("= ") {
+            parts.message = message.to_string();
+        }
+    }
+    if parts.message.is_empty() {
+        parts.message = "invalid liquid syntax".to_string();
+    }
+    parts
+}
+
+fn context_value<'a>(parts: &'a LiquidErrorParts, key: &str) -> Option<&'a str> {
+    parts
+        .context
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+fn line_column(source: &str, index: usize) -> (usize, usize) {
+    let before = &source[..index];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, source[line_start..index].chars().count() + 1)
+}
+
+/// find_in_source finds text from a liquid trace in the source. Liquid normalises whitespace in
+/// traces, so when there is no exact match lines are compared ignoring whitespace.
+fn find_in_source(source: &str, needle: &str) -> Option<(usize, usize)> {
+    if needle.is_empty() {
+        return None;
+    }
+    if let Some(index) = source.find(needle) {
+        return Some(line_column(source, index));
+    }
+    let squash = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    let needle = squash(needle);
+    source.lines().enumerate().find_map(|(i, line)| {
+        squash(line)
+            .contains(&needle)
+            .then(|| (i + 1, line.len() - line.trim_start().len() + 1))
+    })
+}
+
+/// find_filter_use finds where a filter is applied, eg `| upcase`.
+fn find_filter_use(source: &str, filter: &str) -> Option<(usize, usize)> {
+    source.match_indices(filter).find_map(|(index, _)| {
+        let piped = source[..index].trim_end().ends_with('|');
+        let whole_word =
+            !source[index + filter.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_');
+        (piped && whole_word).then(|| line_column(source, index))
+    })
+}
+
+fn locate(
+    source: &str,
+    parts: &LiquidErrorParts,
+    skip_partial_traces: bool,
+) -> Option<(usize, usize)> {
+    if parts.position.is_some() {
+        return parts.position;
+    }
+    let filter = context_value(parts, "requested filter")
+        .or_else(|| context_value(parts, "filter").and_then(|f| f.split_whitespace().next()));
+    if let Some(position) = filter.and_then(|filter| find_filter_use(source, filter)) {
+        return Some(position);
+    }
+    if let (Some(variable), Some(index)) = (
+        context_value(parts, "variable"),
+        context_value(parts, "requested index"),
+    ) {
+        if let Some(position) = find_in_source(source, &format!("{}.{}", variable, index)) {
+            return Some(position);
+        }
+    }
+    if let Some(position) =
+        context_value(parts, "requested variable").and_then(|v| find_in_source(source, v))
+    {
+        return Some(position);
+    }
+    parts
+        .traces
+        .iter()
+        .filter(|trace| *trace != "Filter error")
+        .filter(|trace| !(skip_partial_traces && partial_name(trace).is_some()))
+        .find_map(|trace| find_in_source(source, trace.trim_end_matches("%}").trim_end()))
+}
+
+/// partial_name returns the partial used by a render or include trace.
+fn partial_name(trace: &str) -> Option<String> {
+    let rest = trace
+        .strip_prefix("{% render ")
+        .or_else(|| trace.strip_prefix("{% include "))?;
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    rest[1..].split(quote).next().map(|name| name.to_string())
+}
+
+/// snippet shows the lines around `line` of the source, with a caret under `column`. Line
+/// numbers are shifted by line_offset so they match the file on disk.
+fn snippet(source: &str, line: usize, column: usize, line_offset: usize) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let first = line.saturating_sub(2).max(1);
+    let last = (line + 1).min(lines.len()).max(line);
+    let width = (last + line_offset).to_string().len();
+    let mut out = format!("{:width$} |\n", "", width = width);
+    for number in first..=last {
+        let text = lines.get(number - 1).copied().unwrap_or_default();
+        out.push_str(&format!(
+            "{:>width$} | {}\n",
+            number + line_offset,
+            text,
+            width = width
+        ));
+        if number == line {
+            out.push_str(&format!(
+                "{:width$} | {}^\n",
+                "",
+                " ".repeat(column.saturating_sub(1)),
+                width = width
+            ));
+        }
+    }
+    out
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// did_you_mean picks the closest candidate to an unknown name, if any is close enough.
+pub fn did_you_mean<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+fn suggestion(parts: &LiquidErrorParts) -> Option<String> {
+    [
+        ("requested filter", "available filters"),
+        ("requested partial", "available partials"),
+        ("requested index", "available indexes"),
+        ("requested variable", "available variables"),
+        ("requested key", "available keys"),
+    ]
+    .iter()
+    .find_map(|(requested, available)| {
+        let requested = context_value(parts, requested)?;
+        // Partials are looked up with the extension appended a second time.
+        let requested = requested
+            .strip_suffix(".liquid.liquid")
+            .map(|name| format!("{}.liquid", name))
+            .unwrap_or_else(|| requested.to_string());
+        let available = context_value(parts, available)?;
+        did_you_mean(&requested, available.split(", "))
+    })
+}
+
+/// template_error explains a liquid error raised while parsing or rendering `source`.
+pub fn template_error(
+    error: &liquid::Error,
+    source: TemplateSource<'_>,
+    ctx: &DiagnosticContext<'_>,
+) -> Diagnostic {
+    let parts = parse_liquid_error(&error.to_string());
+
+    let mut chain = vec![ctx.document.to_string()];
+    if source.name != ctx.document {
+        chain.push(source.name.clone());
+    }
+    let partials: Vec<String> = parts
+        .traces
+        .iter()
+        .rev()
+        .filter_map(|trace| partial_name(trace))
+        .filter(|name| ctx.partials.contains_key(name))
+        .collect();
+    for partial in &partials {
+        chain.push(format!("{}/{}", ctx.themes_dir, partial));
+    }
+
+    // When the error happened inside a partial, point at the partial rather than the caller.
+    let innermost_partial = partials
+        .last()
+        .and_then(|name| ctx.partials.get(name).map(|text| (name, text)));
+    let (file, text, line_offset) = match innermost_partial {
+        Some((name, text)) => (format!("{}/{}", ctx.themes_dir, name), text.as_str(), 0),
+        None => (source.name.clone(), source.text, source.line_offset),
+    };
+
+    let position = locate(text, &parts, innermost_partial.is_some());
+    let snippet = position.map(|(line, column)| snippet(text, line, column, line_offset));
+
+    Diagnostic {
+        message: parts.message.clone(),
+        file,
+        line: position.map(|(line, _)| line + line_offset),
+        column: position.map(|(_, column)| column),
+        snippet,
+        details: parts.context.clone(),
+        chain,
+        suggestion: suggestion(&parts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frontmatter_line_offset_counts_the_frontmatter_and_blank_lines() {
+        assert_eq!(
+            frontmatter_line_offset("---\ntitle: Hi\n---\n\n# Hello\n"),
+            4
+        );
+        assert_eq!(frontmatter_line_offset("---\n---\n# Hello\n"), 2);
+        assert_eq!(frontmatter_line_offset("# Hello\n"), 0);
+        assert_eq!(frontmatter_line_offset("\n\n# Hello\n"), 2);
+        // An unclosed block only loses its opening delimiter.
+        assert_eq!(frontmatter_line_offset("---\ntitle: Hi\n# Hello\n"), 1);
+    }
+
+    #[test]
+    fn frontmatter_line_offset_matches_what_gray_matter_strips() {
+        let matter = gray_matter::Matter::<gray_matter::engine::YAML>::new();
+        for contents in [
+            "---\ntitle: Hi\n---\n\n# Hello\nworld\n",
+            "---\ntitle: Hi\n# Hello\nworld\n",
+            "---\n\ntitle: Hi\n# Hello\n",
+            "\n# Hello\nworld\n",
+        ] {
+            let body = matter.parse(contents).content;
+            let first_body_line = body.lines().next().unwrap();
+            let offset = frontmatter_line_offset(contents);
+            assert_eq!(
+                contents.lines().nth(offset),
+                Some(first_body_line),
+                "{:?}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn did_you_mean_picks_the_closest_candidate() {
+        let filters = ["where_glob", "relative_url", "absolute_url", "sort"];
+        assert_eq!(
+            did_you_mean("where_gob", filters.into_iter()),
+            Some("where_glob".to_string())
+        );
+        assert_eq!(
+            did_you_mean("relativ_url", filters.into_iter()),
+            Some("relative_url".to_string())
+        );
+        assert_eq!(did_you_mean("markdown", filters.into_iter()), None);
+        assert_eq!(did_you_mean("x", std::iter::empty()), None);
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
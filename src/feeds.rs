@@ -0,0 +1,506 @@
+use std::collections::HashSet;
+
+use eyre::WrapErr;
+use indexmap::IndexMap;
+use liquid::model::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::hierarchy::PageRef;
+use crate::i18n::language_config;
+use crate::sitemap::file_modified;
+use crate::sort::{as_date_time, safe_property_getter, sort_by_spec};
+use crate::taxonomy::LanguageTaxonomies;
+use crate::urls::{absolute_root_relative_urls, absolute_url};
+use crate::where_glob::glob_matches;
+use crate::{public_dir, write_to_location, Config, DocumentData};
+
+/// FeedFormat is a kind of feed file, selected with the `formats` list of a feed.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    /// Rss writes an RSS 2.0 `feed.xml`.
+    Rss,
+    /// Atom writes an Atom 1.0 `atom.xml`.
+    Atom,
+    /// Json writes a JSON Feed 1.1 `feed.json`.
+    Json,
+}
+
+impl FeedFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "feed.xml",
+            FeedFormat::Atom => "atom.xml",
+            FeedFormat::Json => "feed.json",
+        }
+    }
+}
+
+/// FeedContent selects what the items of a feed hold.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// Full is the whole rendered content of the document.
+    #[default]
+    Full,
+    /// Excerpt is the `excerpt` frontmatter, or else the first paragraph of the content.
+    Excerpt,
+}
+
+fn default_formats() -> Vec<FeedFormat> {
+    vec![FeedFormat::Rss, FeedFormat::Atom, FeedFormat::Json]
+}
+
+fn default_feed_path() -> String {
+    "/".to_string()
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// FeedConfig is one entry of the `feeds:` config list. Items are the newest documents of a
+/// collection, by their `date` frontmatter, eg
+///
+/// ``` yaml
+/// feeds:
+///   - collection: blog/**/*.md
+///     path: /blog/
+///     formats: [rss, atom]
+///     limit: 10
+///     content: excerpt
+///     taxonomies: [tags]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FeedConfig {
+    /// collection is a glob matched against source paths relative to the site root.
+    pub collection: String,
+    /// filter keeps the documents whose properties match the given globs, as `where_glob` does.
+    #[serde(default, rename = "where")]
+    pub filter: IndexMap<String, String>,
+    /// path is the directory the feed files are written to, eg `/blog/` for `/blog/feed.xml`.
+    #[serde(default = "default_feed_path")]
+    pub path: String,
+    /// title defaults to the site title.
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// author is used for items without an `author` frontmatter.
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default = "default_formats")]
+    pub formats: Vec<FeedFormat>,
+    /// limit is the most items in a feed.
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub content: FeedContent,
+    /// taxonomies also get a feed per term, written next to the term page, eg
+    /// `/tags/rust/feed.xml`.
+    #[serde(default)]
+    pub taxonomies: Vec<String>,
+}
+
+/// Author is the `author` frontmatter, either a name or a mapping with a name, email and url.
+#[derive(Deserialize, Debug, Clone, Default)]
+struct Author {
+    name: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+struct FeedItem {
+    file_path: String,
+    title: String,
+    url: String,
+    date: Option<DateTime>,
+    updated: Option<DateTime>,
+    author: Option<Author>,
+    content: String,
+}
+
+/// Feed is a feed file to write, with its items newest first.
+struct Feed<'a> {
+    title: String,
+    description: String,
+    /// url is the page the feed is about, and feed_url the feed file itself.
+    url: String,
+    feed_url: String,
+    author: Option<&'a str>,
+    items: Vec<&'a FeedItem>,
+}
+
+fn frontmatter_date(frontmatter: &Value, key: &str) -> eyre::Result<Option<DateTime>> {
+    match frontmatter.get(key) {
+        Some(value) => Ok(as_date_time(&liquid::model::to_value(value)?)),
+        None => Ok(None),
+    }
+}
+
+fn frontmatter_author(frontmatter: &Value) -> Option<Author> {
+    match frontmatter.get("author")? {
+        Value::String(name) => Some(Author {
+            name: name.clone(),
+            ..Author::default()
+        }),
+        value => serde_yaml::from_value(value.clone()).ok(),
+    }
+}
+
+/// first_paragraph cuts rendered html after its first paragraph.
+fn first_paragraph(html: &str) -> String {
+    match html.find("</p>") {
+        Some(end) => html[..end + "</p>".len()].to_string(),
+        None => html.to_string(),
+    }
+}
+
+pub(crate) fn rfc3339(date: &DateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%:z")
+        .unwrap_or_else(|_| date.to_string())
+}
+
+pub(crate) fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// collect_items returns the documents of a feed's collection as feed items, newest first.
+fn collect_items(
+    root_dir: &str,
+    config: &Config,
+    feed: &FeedConfig,
+    documents: &[DocumentData],
+) -> eyre::Result<Vec<FeedItem>> {
+    let mut seen = HashSet::new();
+    let mut candidates = vec![];
+    for document in documents {
+        // Paginated listings are rendered once per page, and only the first counts.
+        if !seen.insert(document.file_path.as_str())
+            || !glob_match::glob_match(&feed.collection, &document.source_path(root_dir))
+        {
+            continue;
+        }
+        let value = liquid::model::to_value(document)?;
+        let matches = feed.filter.iter().all(|(property, pattern)| {
+            glob_matches(&safe_property_getter(&value, property), pattern)
+        });
+        if matches {
+            candidates.push((value, document));
+        }
+    }
+    let candidates = sort_by_spec(candidates, "frontmatter.date desc, permalink")?;
+
+    let mut items = vec![];
+    for document in candidates {
+        let base_url = language_config(config, &document.lang).base_url;
+        // Documents without a date are dated by their source file. Generated documents have
+        // none, and are left out rather than dated by the build.
+        let date = match frontmatter_date(&document.frontmatter, "date")
+            .wrap_err(format!("invalid `date` in {}", document.file_path))?
+        {
+            Some(date) => date,
+            None => match file_modified(&document.file_path) {
+                Some(modified) => modified,
+                None => continue,
+            },
+        };
+        // Feed readers show the content away from the site, so its links need to be absolute.
+        let content = match feed.content {
+            FeedContent::Full => absolute_root_relative_urls(&document.content, &base_url),
+            FeedContent::Excerpt => match document.frontmatter.get("excerpt") {
+                Some(Value::String(excerpt)) => format!("<p>{}</p>", xml_escape(excerpt)),
+                _ => absolute_root_relative_urls(&first_paragraph(&document.content), &base_url),
+            },
+        };
+        items.push(FeedItem {
+            file_path: document.file_path.clone(),
+            title: PageRef::from(document).title,
+            url: absolute_url(&base_url, &document.permalink),
+            date: Some(date),
+            updated: frontmatter_date(&document.frontmatter, "updated")
+                .wrap_err(format!("invalid `updated` in {}", document.file_path))?,
+            author: frontmatter_author(&document.frontmatter),
+            content,
+        });
+    }
+    // Dates taken from source files can be newer than those of the documents sorted before them.
+    items.sort_by_key(|item| std::cmp::Reverse(item.date));
+    Ok(items)
+}
+
+fn rss(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    xml.push_str("<channel>\n");
+    xml.push_str(&format!("<title>{}</title>\n", xml_escape(&feed.title)));
+    xml.push_str(&format!("<link>{}</link>\n", xml_escape(&feed.url)));
+    xml.push_str(&format!(
+        "<description>{}</description>\n",
+        xml_escape(&feed.description)
+    ));
+    xml.push_str(&format!(
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        xml_escape(&feed.feed_url)
+    ));
+    if let Some(date) = feed.items.iter().find_map(|item| item.date) {
+        xml.push_str(&format!(
+            "<lastBuildDate>{}</lastBuildDate>\n",
+            date.to_rfc2822()
+        ));
+    }
+    for item in &feed.items {
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", xml_escape(&item.title)));
+        xml.push_str(&format!("<link>{}</link>\n", xml_escape(&item.url)));
+        xml.push_str(&format!(
+            "<guid isPermaLink=\"true\">{}</guid>\n",
+            xml_escape(&item.url)
+        ));
+        if let Some(date) = item.date {
+            xml.push_str(&format!("<pubDate>{}</pubDate>\n", date.to_rfc2822()));
+        }
+        if let Some(author) = item
+            .author
+            .as_ref()
+            .map(|a| a.name.as_str())
+            .or(feed.author)
+        {
+            xml.push_str(&format!(
+                "<dc:creator>{}</dc:creator>\n",
+                xml_escape(author)
+            ));
+        }
+        xml.push_str(&format!(
+            "<description>{}</description>\n",
+            xml_escape(&item.content)
+        ));
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn atom_author(author: &Author) -> String {
+    let mut xml = format!("<author><name>{}</name>", xml_escape(&author.name));
+    if let Some(email) = &author.email {
+        xml.push_str(&format!("<email>{}</email>", xml_escape(email)));
+    }
+    if let Some(url) = &author.url {
+        xml.push_str(&format!("<uri>{}</uri>", xml_escape(url)));
+    }
+    xml.push_str("</author>\n");
+    xml
+}
+
+fn atom(feed: &Feed) -> String {
+    // Atom requires an updated date on the feed and on every entry.
+    let updated = |item: &FeedItem| item.updated.or(item.date);
+    let feed_updated = feed
+        .items
+        .iter()
+        .filter_map(|item| updated(item))
+        .max()
+        .unwrap_or_else(DateTime::now);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<title>{}</title>\n", xml_escape(&feed.title)));
+    if !feed.description.is_empty() {
+        xml.push_str(&format!(
+            "<subtitle>{}</subtitle>\n",
+            xml_escape(&feed.description)
+        ));
+    }
+    xml.push_str(&format!("<id>{}</id>\n", xml_escape(&feed.feed_url)));
+    xml.push_str(&format!(
+        "<link href=\"{}\" rel=\"self\"/>\n",
+        xml_escape(&feed.feed_url)
+    ));
+    xml.push_str(&format!("<link href=\"{}\"/>\n", xml_escape(&feed.url)));
+    xml.push_str(&format!("<updated>{}</updated>\n", rfc3339(&feed_updated)));
+    xml.push_str(&atom_author(&Author {
+        name: feed.author.unwrap_or(&feed.title).to_string(),
+        ..Author::default()
+    }));
+    for item in &feed.items {
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<title>{}</title>\n", xml_escape(&item.title)));
+        xml.push_str(&format!("<id>{}</id>\n", xml_escape(&item.url)));
+        xml.push_str(&format!("<link href=\"{}\"/>\n", xml_escape(&item.url)));
+        xml.push_str(&format!(
+            "<updated>{}</updated>\n",
+            rfc3339(&updated(item).unwrap_or(feed_updated))
+        ));
+        if let Some(date) = item.date {
+            xml.push_str(&format!("<published>{}</published>\n", rfc3339(&date)));
+        }
+        if let Some(author) = &item.author {
+            xml.push_str(&atom_author(author));
+        }
+        xml.push_str(&format!(
+            "<content type=\"html\">{}</content>\n",
+            xml_escape(&item.content)
+        ));
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn json_feed(feed: &Feed) -> eyre::Result<String> {
+    let json_author = |author: &Author| {
+        let mut value = serde_json::json!({ "name": author.name });
+        if let Some(url) = &author.url {
+            value["url"] = url.clone().into();
+        }
+        value
+    };
+    let items: Vec<serde_json::Value> = feed
+        .items
+        .iter()
+        .map(|item| {
+            let mut value = serde_json::json!({
+                "id": item.url,
+                "url": item.url,
+                "title": item.title,
+                "content_html": item.content,
+            });
+            if let Some(date) = item.date {
+                value["date_published"] = rfc3339(&date).into();
+            }
+            if let Some(updated) = item.updated {
+                value["date_modified"] = rfc3339(&updated).into();
+            }
+            if let Some(author) = &item.author {
+                value["authors"] = serde_json::json!([json_author(author)]);
+            }
+            value
+        })
+        .collect();
+
+    let mut json = serde_json::json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": feed.title,
+        "home_page_url": feed.url,
+        "feed_url": feed.feed_url,
+    });
+    if !feed.description.is_empty() {
+        json["description"] = feed.description.clone().into();
+    }
+    if let Some(author) = feed.author {
+        json["authors"] = serde_json::json!([{ "name": author }]);
+    }
+    json["items"] = items.into();
+    Ok(serde_json::to_string_pretty(&json)? + "\n")
+}
+
+fn write_feed(
+    root_dir: &str,
+    config: &Config,
+    feed_config: &FeedConfig,
+    path: &str,
+    title: String,
+    items: Vec<&FeedItem>,
+) -> eyre::Result<()> {
+    let path = format!("/{}/", path.trim_matches('/')).replace("//", "/");
+    for format in &feed_config.formats {
+        let file = format!("{}{}", path, format.file_name());
+        let feed = Feed {
+            description: feed_config.description.clone().unwrap_or_default(),
+            url: absolute_url(&config.base_url, &path),
+            feed_url: absolute_url(&config.base_url, &file),
+            author: feed_config.author.as_deref(),
+            items: items.clone(),
+            title: title.clone(),
+        };
+        let contents = match format {
+            FeedFormat::Rss => rss(&feed),
+            FeedFormat::Atom => atom(&feed),
+            FeedFormat::Json => json_feed(&feed)?,
+        };
+        write_to_location(
+            format!("{}{}", public_dir(root_dir), file),
+            contents.as_bytes(),
+        )
+        .wrap_err(format!("unable to write the feed {}", file))?;
+    }
+    Ok(())
+}
+
+/// write_feeds writes every configured feed, and the feeds of their taxonomy terms, from the
+/// rendered documents.
+pub fn write_feeds(
+    root_dir: &str,
+    config: &Config,
+    taxonomies: &LanguageTaxonomies,
+    documents: &[DocumentData],
+) -> eyre::Result<()> {
+    for feed in &config.feeds {
+        let items = collect_items(root_dir, config, feed, documents).wrap_err(format!(
+            "unable to collect the feed of `{}`",
+            feed.collection
+        ))?;
+        let title = feed.title.clone().unwrap_or_else(|| config.title.clone());
+        write_feed(
+            root_dir,
+            config,
+            feed,
+            &feed.path,
+            title.clone(),
+            items.iter().take(feed.limit).collect(),
+        )?;
+
+        for taxonomy in &feed.taxonomies {
+            if !config.taxonomies.contains_key(taxonomy) {
+                return Err(eyre::eyre!(
+                    "feed of `{}` lists the taxonomy `{}`, which is not configured",
+                    feed.collection,
+                    taxonomy
+                ));
+            }
+            // Every language has its own terms, and so its own term feeds.
+            let terms = taxonomies
+                .values()
+                .filter_map(|language_taxonomies| language_taxonomies.get(taxonomy))
+                .flat_map(|terms| terms.values());
+            for term in terms {
+                let pages: HashSet<&str> =
+                    term.pages.iter().map(|p| p.file_path.as_str()).collect();
+                let term_items: Vec<&FeedItem> = items
+                    .iter()
+                    .filter(|item| pages.contains(item.file_path.as_str()))
+                    .take(feed.limit)
+                    .collect();
+                if term_items.is_empty() {
+                    continue;
+                }
+                write_feed(
+                    root_dir,
+                    config,
+                    feed,
+                    &term.permalink,
+                    format!("{} - {}", title, term.name),
+                    term_items,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
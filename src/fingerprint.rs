@@ -0,0 +1,329 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use base64::Engine;
+use indexmap::IndexMap;
+use liquid_core::parser::FilterArguments;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::{Display_filter, Filter, FilterReflection, ParseFilter};
+use liquid_core::{Value, ValueView};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha384};
+
+use crate::link::normalize_source_path;
+use crate::urls::{expect_no_arguments, input_as_string, relative_url};
+use crate::write_to_location;
+
+/// FingerprintConfig is the `fingerprint:` config section. Copied files matching its globs are
+/// written with a hash of their content in their name, eg `css/style.css` becomes
+/// `css/style.3f2a9c1b.css`, so they can be cached forever:
+///
+/// ``` yaml
+/// fingerprint:
+///   files: ["**/*.css", "js/**/*.js"]
+/// ```
+///
+/// Templates link to them with `{{ "css/style.css" | asset_url }}`, and get their subresource
+/// integrity hash with `{{ "css/style.css" | integrity }}`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct FingerprintConfig {
+    /// files are globs of the source paths to fingerprint, relative to the site root.
+    pub files: Vec<String>,
+    /// hash_length is the number of hex characters of the hash added to file names.
+    pub hash_length: usize,
+    /// manifest is the json file in the output directory which maps source paths to the
+    /// fingerprinted files, for tools outside quickstatic.
+    pub manifest: String,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        FingerprintConfig {
+            files: vec![],
+            hash_length: 8,
+            manifest: "asset-manifest.json".to_string(),
+        }
+    }
+}
+
+/// Asset is a fingerprinted file.
+#[derive(Serialize, Debug, Clone)]
+pub struct Asset {
+    /// path is where the file was written, relative to the output directory.
+    pub path: String,
+    /// integrity is the subresource integrity hash of the file, eg `sha384-...`.
+    pub integrity: String,
+}
+
+/// Assets collects the files fingerprinted while the site is copied, by their source path.
+pub struct Assets {
+    config: Option<FingerprintConfig>,
+    root_dir: String,
+    pub manifest: IndexMap<String, Asset>,
+}
+
+/// fingerprinted_name adds the hash to a file name before its extension, eg `style.3f2a9c1b.css`.
+fn fingerprinted_name(name: &str, hash: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{}.{}.{}", stem, hash, extension)
+        }
+        _ => format!("{}.{}", name, hash),
+    }
+}
+
+impl Assets {
+    pub fn new(root_dir: &str, config: Option<FingerprintConfig>) -> Self {
+        Assets {
+            config,
+            root_dir: root_dir.to_string(),
+            manifest: IndexMap::new(),
+        }
+    }
+
+    fn source_path(&self, path: &Path) -> String {
+        let path = path.to_string_lossy();
+        normalize_source_path(path.strip_prefix(&self.root_dir).unwrap_or(&path))
+    }
+
+    /// fingerprints is true for files matching the `files` globs.
+    pub fn fingerprints(&self, path: &Path) -> bool {
+        let Some(config) = &self.config else {
+            return false;
+        };
+        let source_path = self.source_path(path);
+        config
+            .files
+            .iter()
+            .any(|glob| glob_match::glob_match(glob, &source_path))
+    }
+
+    /// write writes the contents of the file at path under its fingerprinted name, next to dest,
+    /// and adds it to the manifest.
+    pub fn write(&mut self, path: &Path, dest: &Path, contents: &[u8]) -> eyre::Result<()> {
+        let hash_length = self.config.as_ref().map_or(8, |config| config.hash_length);
+        let digest = Sha384::digest(contents);
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let hash = &hex[..hash_length.clamp(1, hex.len())];
+
+        let source_path = self.source_path(path);
+        let name = fingerprinted_name(
+            &dest.file_name().unwrap_or_default().to_string_lossy(),
+            hash,
+        );
+        let path = match source_path.rsplit_once('/') {
+            Some((dir, _)) => format!("{}/{}", dir, name),
+            None => name.clone(),
+        };
+        write_to_location(
+            dest.with_file_name(&name).to_string_lossy().to_string(),
+            contents,
+        )?;
+        self.manifest.insert(
+            source_path,
+            Asset {
+                path,
+                integrity: format!(
+                    "sha384-{}",
+                    base64::engine::general_purpose::STANDARD.encode(digest)
+                ),
+            },
+        );
+        Ok(())
+    }
+
+    /// write_manifest writes the manifest into the public directory, when fingerprinting is on.
+    pub fn write_manifest(&self, public_dir: &str) -> eyre::Result<()> {
+        let Some(config) = &self.config else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&self.manifest)? + "\n";
+        write_to_location(
+            format!("{}/{}", public_dir, config.manifest.trim_start_matches('/')),
+            json.as_bytes(),
+        )
+    }
+}
+
+// AssetUrl Filter
+//
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "asset_url",
+    description = "Outputs the url of a copied file, under its fingerprinted name when it has one.",
+    parsed(AssetUrlFilter)
+)]
+pub struct AssetUrl {
+    pub manifest: Arc<IndexMap<String, Asset>>,
+    pub base_path: String,
+}
+
+impl ParseFilter for AssetUrl {
+    fn parse(&self, args: FilterArguments) -> Result<Box<dyn Filter>> {
+        expect_no_arguments(args)?;
+        Ok(Box::new(AssetUrlFilter {
+            manifest: self.manifest.clone(),
+            base_path: self.base_path.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "asset_url"]
+struct AssetUrlFilter {
+    manifest: Arc<IndexMap<String, Asset>>,
+    base_path: String,
+}
+
+impl Filter for AssetUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        let path = input_as_string(input)?;
+        // Files which are not fingerprinted keep their name.
+        let url = match self.manifest.get(&normalize_source_path(&path)) {
+            Some(asset) => relative_url(&self.base_path, &asset.path),
+            None => relative_url(&self.base_path, &path),
+        };
+        Ok(Value::scalar(url))
+    }
+}
+
+// Integrity Filter
+//
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "integrity",
+    description = "Outputs the subresource integrity hash of a fingerprinted file.",
+    parsed(IntegrityFilter)
+)]
+pub struct Integrity {
+    pub manifest: Arc<IndexMap<String, Asset>>,
+}
+
+impl ParseFilter for Integrity {
+    fn parse(&self, args: FilterArguments) -> Result<Box<dyn Filter>> {
+        expect_no_arguments(args)?;
+        Ok(Box::new(IntegrityFilter {
+            manifest: self.manifest.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "integrity"]
+struct IntegrityFilter {
+    manifest: Arc<IndexMap<String, Asset>>,
+}
+
+impl Filter for IntegrityFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        let path = input_as_string(input)?;
+        let asset = self
+            .manifest
+            .get(&normalize_source_path(&path))
+            .ok_or_else(|| {
+                liquid_core::Error::with_msg("file is not fingerprinted")
+                    .context("path", path.clone())
+                    .context(
+                        "cause",
+                        "only files matching the `fingerprint` globs have a hash",
+                    )
+            })?;
+        Ok(Value::scalar(asset.integrity.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn fingerprinted_name_goes_before_the_extension() {
+        assert_eq!(fingerprinted_name("style.css", "abc"), "style.abc.css");
+        assert_eq!(fingerprinted_name("app.min.js", "abc"), "app.min.abc.js");
+        assert_eq!(fingerprinted_name(".htaccess", "abc"), ".htaccess.abc");
+        assert_eq!(fingerprinted_name("LICENSE", "abc"), "LICENSE.abc");
+    }
+
+    #[test]
+    fn write_adds_the_file_to_the_manifest() {
+        let root = std::env::temp_dir().join(format!("quickstatic-assets-{}", std::process::id()));
+        let root_dir = root.to_string_lossy().to_string();
+        let public = root.join("_quickstatic/public");
+        let config = FingerprintConfig {
+            files: vec!["**/*.css".to_string()],
+            ..FingerprintConfig::default()
+        };
+        let mut assets = Assets::new(&root_dir, Some(config));
+        let source = root.join("css/style.css");
+        assert!(assets.fingerprints(&source));
+        assert!(!assets.fingerprints(&root.join("js/app.js")));
+
+        assets
+            .write(&source, &public.join("css/style.css"), b"body{}")
+            .unwrap();
+        assets.write_manifest(&public.to_string_lossy()).unwrap();
+        let asset = &assets.manifest["css/style.css"];
+        let written = public.join(&asset.path).is_file();
+        let manifest = fs::read_to_string(public.join("asset-manifest.json")).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(written);
+        assert!(asset.path.starts_with("css/style."));
+        assert!(asset.path.ends_with(".css"));
+        assert_eq!(asset.path.len(), "css/style..css".len() + 8);
+        assert!(asset.integrity.starts_with("sha384-"));
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["css/style.css"]["path"], asset.path.as_str());
+    }
+
+    #[test]
+    fn filters_read_the_manifest() {
+        let mut manifest = IndexMap::new();
+        manifest.insert(
+            "css/style.css".to_string(),
+            Asset {
+                path: "css/style.3f2a9c1b.css".to_string(),
+                integrity: "sha384-abc".to_string(),
+            },
+        );
+        let manifest = Arc::new(manifest);
+        let parser = liquid::ParserBuilder::with_stdlib()
+            .filter(AssetUrl {
+                manifest: manifest.clone(),
+                base_path: "/docs".to_string(),
+            })
+            .filter(Integrity { manifest })
+            .build()
+            .unwrap();
+        let render = |template: &str| {
+            parser
+                .parse(template)
+                .and_then(|template| template.render(&liquid::Object::new()))
+        };
+
+        assert_eq!(
+            render(r#"{{ "/css/style.css" | asset_url }} {{ "css/style.css" | integrity }}"#)
+                .unwrap(),
+            "/docs/css/style.3f2a9c1b.css sha384-abc"
+        );
+        assert_eq!(
+            render(r#"{{ "js/app.js" | asset_url }}"#).unwrap(),
+            "/docs/js/app.js"
+        );
+        assert!(render(r#"{{ "js/app.js" | integrity }}"#).is_err());
+    }
+}
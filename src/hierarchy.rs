@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::sort::sort_by_spec;
+use crate::DocumentData;
+
+/// NAVIGATION_ORDER is how children, siblings and previous/next pages are ordered: by weight,
+/// then chronologically, then by title.
+const NAVIGATION_ORDER: &str = "frontmatter.weight, frontmatter.date, frontmatter.title, permalink";
+
+/// PageRef is a short reference to another page, eg for navigation or related content.
+#[derive(Clone, Debug, Serialize)]
+pub struct PageRef {
+    pub title: String,
+    pub permalink: String,
+    pub frontmatter: Value,
+}
+
+/// Navigation places a document in the site tree. It is flattened into `this`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Navigation {
+    /// parent is the index of the closest section above the document.
+    pub parent: Option<PageRef>,
+    pub children: Vec<PageRef>,
+    /// siblings are the other children of the parent.
+    pub siblings: Vec<PageRef>,
+    pub previous: Option<PageRef>,
+    pub next: Option<PageRef>,
+    /// breadcrumbs runs from the root of the site down to the document itself.
+    pub breadcrumbs: Vec<PageRef>,
+}
+
+/// Hierarchy is the tree of html documents, built from their source paths. Sections are
+/// directories with an index document, eg `blog/index.md` is the parent of `blog/hello.md` and
+/// `blog/world/index.md`. Every language has a tree of its own, built from the source paths
+/// without the language, so `fr/blog/hello.md` is a child of `fr/blog/index.md`.
+pub struct Hierarchy {
+    pages: HashMap<String, PageRef>,
+    parents: HashMap<String, String>,
+    /// children holds the ordered children of every section index.
+    children: HashMap<String, Vec<String>>,
+}
+
+fn is_index(source_path: &str) -> bool {
+    let name = source_path.rsplit('/').next().unwrap_or(source_path);
+    name.split('.').next() == Some("index")
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+impl From<&DocumentData> for PageRef {
+    // The title falls back to the name of the source file, or of its directory for index files.
+    fn from(document: &DocumentData) -> Self {
+        let source_path = document.translation_key.as_str();
+        let title = document
+            .frontmatter
+            .get("title")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                let path = if is_index(source_path) {
+                    parent_dir(source_path)
+                } else {
+                    source_path
+                };
+                let name = path.rsplit('/').next().unwrap_or(path);
+                name.split('.').next().unwrap_or(name).to_string()
+            });
+        PageRef {
+            title,
+            permalink: document.permalink.clone(),
+            frontmatter: document.frontmatter.clone(),
+        }
+    }
+}
+
+impl Hierarchy {
+    pub fn new(root_dir: &str, documents: &[DocumentData]) -> eyre::Result<Self> {
+        let documents: Vec<(String, &DocumentData)> = documents
+            .iter()
+            .filter(|d| d.file_destination_path.ends_with(".html"))
+            .map(|d| (d.source_path(root_dir), d))
+            .collect();
+
+        // sections maps each directory with an index document to that document, per language.
+        let sections: HashMap<(&str, &str), &str> = documents
+            .iter()
+            .filter(|(_, document)| is_index(&document.translation_key))
+            .map(|(path, document)| {
+                let dir = parent_dir(&document.translation_key);
+                ((document.lang.as_str(), dir), path.as_str())
+            })
+            .collect();
+
+        let mut parents = HashMap::new();
+        for (path, document) in &documents {
+            let key = document.translation_key.as_str();
+            let mut dir = if is_index(key) {
+                let own_dir = parent_dir(key);
+                if own_dir.is_empty() {
+                    // The site index is the root of the tree.
+                    continue;
+                }
+                parent_dir(own_dir)
+            } else {
+                parent_dir(key)
+            };
+            loop {
+                if let Some(section) = sections.get(&(document.lang.as_str(), dir)) {
+                    parents.insert(path.clone(), section.to_string());
+                    break;
+                }
+                if dir.is_empty() {
+                    break;
+                }
+                dir = parent_dir(dir);
+            }
+        }
+
+        let mut unordered_children: HashMap<String, Vec<(liquid::model::Value, String)>> =
+            HashMap::new();
+        for (path, document) in &documents {
+            if let Some(parent) = parents.get(path) {
+                unordered_children
+                    .entry(parent.clone())
+                    .or_default()
+                    .push((liquid::model::to_value(document)?, path.clone()));
+            }
+        }
+        let mut children = HashMap::new();
+        for (parent, items) in unordered_children {
+            children.insert(parent, sort_by_spec(items, NAVIGATION_ORDER)?);
+        }
+
+        let pages = documents
+            .iter()
+            .map(|(path, document)| (path.clone(), PageRef::from(*document)))
+            .collect();
+        Ok(Hierarchy {
+            pages,
+            parents,
+            children,
+        })
+    }
+
+    fn refs(&self, paths: &[String]) -> Vec<PageRef> {
+        paths
+            .iter()
+            .filter_map(|path| self.pages.get(path).cloned())
+            .collect()
+    }
+
+    /// navigation returns the place of the document with the given source path in the tree.
+    pub fn navigation(&self, source_path: &str) -> Navigation {
+        let Some(page) = self.pages.get(source_path) else {
+            return Navigation::default();
+        };
+
+        let mut breadcrumbs = vec![page.clone()];
+        let mut current = source_path;
+        while let Some(parent) = self.parents.get(current) {
+            breadcrumbs.extend(self.pages.get(parent).cloned());
+            current = parent;
+        }
+        breadcrumbs.reverse();
+
+        let parent = self.parents.get(source_path);
+        let empty = vec![];
+        let family = parent
+            .and_then(|parent| self.children.get(parent))
+            .unwrap_or(&empty);
+        let position = family.iter().position(|path| path == source_path);
+        let siblings: Vec<String> = family
+            .iter()
+            .filter(|path| *path != source_path)
+            .cloned()
+            .collect();
+
+        Navigation {
+            parent: parent.and_then(|parent| self.pages.get(parent).cloned()),
+            children: self.refs(self.children.get(source_path).unwrap_or(&empty)),
+            siblings: self.refs(&siblings),
+            previous: position
+                .and_then(|i| i.checked_sub(1))
+                .and_then(|i| self.pages.get(&family[i]).cloned()),
+            next: position
+                .and_then(|i| family.get(i + 1))
+                .and_then(|path| self.pages.get(path).cloned()),
+            breadcrumbs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    fn permalinks(pages: &[PageRef]) -> Vec<&str> {
+        pages.iter().map(|page| page.permalink.as_str()).collect()
+    }
+
+    fn multilingual_site() -> Hierarchy {
+        let config = test_config("languages:\n  fr: {}");
+        let documents = [
+            ("index.md", "index.html", "title: Home"),
+            ("blog/index.md", "blog/index.html", "title: Blog"),
+            ("blog/one.md", "blog/one/index.html", "weight: 1"),
+            ("blog/two.md", "blog/two/index.html", "weight: 2"),
+            ("fr/index.md", "fr/index.html", "title: Accueil"),
+            ("fr/blog/index.md", "fr/blog/index.html", "title: Blogue"),
+            ("fr/blog/one.md", "fr/blog/one/index.html", "{}"),
+            ("blog/two.fr.md", "fr/blog/two/index.html", "{}"),
+        ]
+        .map(|(source, destination, frontmatter)| {
+            test_document(&config, source, destination, frontmatter)
+        });
+        Hierarchy::new("/site", &documents).unwrap()
+    }
+
+    #[test]
+    fn navigation_orders_children_and_siblings() {
+        let hierarchy = multilingual_site();
+        let blog = hierarchy.navigation("blog/index.md");
+        assert_eq!(permalinks(&blog.children), ["/blog/one/", "/blog/two/"]);
+        let one = hierarchy.navigation("blog/one.md");
+        assert_eq!(permalinks(&one.siblings), ["/blog/two/"]);
+        assert!(one.previous.is_none());
+        assert_eq!(one.next.unwrap().permalink, "/blog/two/");
+    }
+
+    #[test]
+    fn every_language_has_its_own_tree() {
+        let hierarchy = multilingual_site();
+        let one = hierarchy.navigation("fr/blog/one.md");
+        assert_eq!(one.parent.unwrap().permalink, "/fr/blog/");
+        assert_eq!(
+            permalinks(&one.breadcrumbs),
+            ["/fr/", "/fr/blog/", "/fr/blog/one/"]
+        );
+        assert_eq!(permalinks(&one.siblings), ["/fr/blog/two/"]);
+        let two = hierarchy.navigation("blog/two.fr.md");
+        assert_eq!(two.parent.unwrap().permalink, "/fr/blog/");
+
+        let blog = hierarchy.navigation("blog/index.md");
+        assert_eq!(blog.parent.unwrap().permalink, "/");
+        assert!(!permalinks(&blog.children).contains(&"/fr/blog/one/"));
+        let home = hierarchy.navigation("index.md");
+        assert_eq!(permalinks(&home.children), ["/blog/"]);
+        let accueil = hierarchy.navigation("fr/index.md");
+        assert_eq!(permalinks(&accueil.children), ["/fr/blog/"]);
+    }
+}
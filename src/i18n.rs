@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use eyre::WrapErr;
+use liquid_core::parser::FilterArguments;
+use liquid_core::Expression;
+use liquid_core::Runtime;
+use liquid_core::{Display_filter, Filter, FilterReflection, ParseFilter};
+use liquid_core::{Value, ValueView};
+use serde::{Deserialize, Serialize};
+
+use crate::urls::{absolute_url, base_path_from_url};
+use crate::{Config, DocumentData};
+
+/// LanguageConfig is one entry of the `languages:` config section. Its values replace the
+/// site-wide ones for documents in that language, eg
+///
+/// ``` yaml
+/// default_language: en
+/// languages:
+///   en:
+///     name: English
+///   fr:
+///     name: Français
+///     title: Documentation
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LanguageConfig {
+    /// name is how the language is shown to readers, eg in a language switcher.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// locale is the `og:locale` of the language's pages, eg `fr_CA`, instead of `seo.locale`.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+/// Translation links a document to the same document in another language.
+#[derive(Clone, Debug, Serialize)]
+pub struct Translation {
+    pub lang: String,
+    pub title: Option<String>,
+    pub permalink: String,
+}
+
+/// Alternate is an entry for the `hreflang` links of a document, eg
+/// `<link rel="alternate" hreflang="fr" href="https://example.org/fr/about/">`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Alternate {
+    pub hreflang: String,
+    pub href: String,
+}
+
+/// DocumentLanguage is the language of a source file, and the source path it has in every
+/// language, which is what translations share.
+pub struct DocumentLanguage {
+    pub lang: String,
+    pub translation_key: String,
+}
+
+fn is_other_language(config: &Config, code: &str) -> bool {
+    code != config.default_language && config.languages.contains_key(code)
+}
+
+/// document_language finds the language of a source path. Translations are either named with
+/// the language before the extensions, eg `about.fr.md`, or live in a directory named after the
+/// language, eg `fr/about.md`. Everything else is in the default language.
+pub fn document_language(config: &Config, source_path: &str) -> DocumentLanguage {
+    let default = || DocumentLanguage {
+        lang: config.default_language.clone(),
+        translation_key: source_path.to_string(),
+    };
+    if config.languages.is_empty() {
+        return default();
+    }
+
+    if let Some((code, rest)) = source_path.split_once('/') {
+        if is_other_language(config, code) {
+            return DocumentLanguage {
+                lang: code.to_string(),
+                translation_key: rest.to_string(),
+            };
+        }
+    }
+
+    let (dir, name) = source_path.rsplit_once('/').unwrap_or(("", source_path));
+    let parts: Vec<&str> = name.split('.').collect();
+    if parts.len() >= 3 && is_other_language(config, parts[1]) {
+        let mut neutral = vec![parts[0]];
+        neutral.extend(&parts[2..]);
+        let neutral = neutral.join(".");
+        return DocumentLanguage {
+            lang: parts[1].to_string(),
+            translation_key: if dir.is_empty() {
+                neutral
+            } else {
+                format!("{}/{}", dir, neutral)
+            },
+        };
+    }
+    default()
+}
+
+/// language_prefix is the directory documents of a language are generated in. The default
+/// language is generated at the root of the site.
+pub fn language_prefix(config: &Config, lang: &str) -> String {
+    if lang == config.default_language {
+        String::new()
+    } else {
+        format!("/{}", lang)
+    }
+}
+
+/// language_config returns the site config as seen by documents in a language, with the values
+/// of its `languages:` entry applied.
+///
+/// Permalinks of a language already start with its prefix, eg `/fr/about/`. A base_url ending
+/// with the prefix, eg `https://example.org/fr/`, is used without it, so urls are not prefixed
+/// twice.
+pub fn language_config(config: &Config, lang: &str) -> Config {
+    let prefix = language_prefix(config, lang);
+    let mut config = config.clone();
+    if let Some(language) = config.languages.get(lang).cloned() {
+        if let Some(title) = language.title {
+            config.title = title;
+        }
+        if let Some(locale) = language.locale {
+            config.seo.locale = Some(locale);
+        }
+        if let Some(base_url) = language.base_url {
+            let site_url = base_url
+                .trim_end_matches('/')
+                .strip_suffix(prefix.as_str())
+                .filter(|_| !prefix.is_empty());
+            config.base_url = match site_url {
+                Some(site_url) => format!("{}/", site_url),
+                None => base_url,
+            };
+            config.base_path = base_path_from_url(&config.base_url);
+        }
+    }
+    config
+}
+
+/// link_translations fills in the translations and hreflang links of every document which exists
+/// in more than one language.
+pub fn link_translations(config: &Config, documents: &mut [DocumentData]) {
+    let mut variants: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, document) in documents.iter().enumerate() {
+        variants
+            .entry(document.translation_key.clone())
+            .or_default()
+            .push(i);
+    }
+
+    let base_urls: HashMap<&str, String> = config
+        .languages
+        .keys()
+        .map(|lang| (lang.as_str(), language_config(config, lang).base_url))
+        .collect();
+    let base_url = |lang: &str| base_urls.get(lang).unwrap_or(&config.base_url).clone();
+
+    for indexes in variants.values_mut().filter(|indexes| indexes.len() > 1) {
+        // Languages are listed in the order of the config, with the default language first.
+        indexes.sort_by_key(|i| {
+            let lang = &documents[*i].lang;
+            (
+                *lang != config.default_language,
+                config.languages.get_index_of(lang),
+            )
+        });
+        let mut alternates: Vec<Alternate> = indexes
+            .iter()
+            .map(|i| Alternate {
+                hreflang: documents[*i].lang.clone(),
+                href: absolute_url(&base_url(&documents[*i].lang), &documents[*i].permalink),
+            })
+            .collect();
+        if let Some(default) = indexes
+            .iter()
+            .find(|i| documents[**i].lang == config.default_language)
+        {
+            alternates.push(Alternate {
+                hreflang: "x-default".to_string(),
+                href: absolute_url(
+                    &base_url(&config.default_language),
+                    &documents[*default].permalink,
+                ),
+            });
+        }
+
+        for i in indexes.iter() {
+            let translations = indexes
+                .iter()
+                .filter(|j| *j != i)
+                .map(|j| Translation {
+                    lang: documents[*j].lang.clone(),
+                    title: documents[*j]
+                        .frontmatter
+                        .get("title")
+                        .and_then(serde_yaml::Value::as_str)
+                        .map(str::to_string),
+                    permalink: documents[*j].permalink.clone(),
+                })
+                .collect();
+            documents[*i].translations = translations;
+            documents[*i].hreflang = alternates.clone();
+        }
+    }
+}
+
+/// Translations holds the strings of every language, keyed by dotted paths such as `nav.home`.
+pub type Translations = HashMap<String, HashMap<String, String>>;
+
+fn flatten(prefix: &str, value: serde_yaml::Value, strings: &mut HashMap<String, String>) {
+    let key = |k: &str| {
+        if prefix.is_empty() {
+            k.to_string()
+        } else {
+            format!("{}.{}", prefix, k)
+        }
+    };
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (k, v) in mapping {
+                let k = match k {
+                    serde_yaml::Value::String(s) => s,
+                    serde_yaml::Value::Number(n) => n.to_string(),
+                    serde_yaml::Value::Bool(b) => b.to_string(),
+                    _ => continue,
+                };
+                flatten(&key(&k), v, strings);
+            }
+        }
+        serde_yaml::Value::String(s) => {
+            strings.insert(prefix.to_string(), s);
+        }
+        serde_yaml::Value::Number(n) => {
+            strings.insert(prefix.to_string(), n.to_string());
+        }
+        serde_yaml::Value::Bool(b) => {
+            strings.insert(prefix.to_string(), b.to_string());
+        }
+        _ => {}
+    }
+}
+
+/// read_translations reads the translation files of the themes, eg `i18n/fr.yaml` in the themes
+/// directory, for the `t` filter.
+pub fn read_translations(themes_dir: &str) -> eyre::Result<Translations> {
+    let mut translations = Translations::new();
+    let dir = Path::new(themes_dir).join("i18n");
+    if !dir.is_dir() {
+        return Ok(translations);
+    }
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let is_yaml = path
+            .extension()
+            .is_some_and(|ext| ext == "yaml" || ext == "yml");
+        let Some(lang) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if !is_yaml {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)?;
+        let value: serde_yaml::Value = serde_yaml::from_str(&contents)
+            .wrap_err(format!("unable to parse translation file {:?}", path))?;
+        let mut strings = HashMap::new();
+        flatten("", value, &mut strings);
+        translations.insert(lang, strings);
+    }
+    Ok(translations)
+}
+
+// T Filter
+//
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "t",
+    description = "Translates a key into the language of the document, eg `{{ \"nav.home\" | t }}`. \
+                   Keyword arguments replace placeholders, eg `{{ \"posts\" | t: count: 3 }}` \
+                   fills in `{count}`.",
+    parsed(TFilter)
+)]
+pub struct T {
+    pub lang: String,
+    pub default_language: String,
+    pub translations: Arc<Translations>,
+}
+
+impl ParseFilter for T {
+    fn parse(&self, mut args: FilterArguments) -> liquid_core::Result<Box<dyn Filter>> {
+        if args.positional.next().is_some() {
+            return Err(
+                liquid_core::Error::with_msg("Invalid number of positional arguments")
+                    .context("cause", "expected at most 0 positional arguments"),
+            );
+        }
+        Ok(Box::new(TFilter {
+            lang: self.lang.clone(),
+            default_language: self.default_language.clone(),
+            translations: self.translations.clone(),
+            placeholders: args
+                .keyword
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "t"]
+struct TFilter {
+    lang: String,
+    default_language: String,
+    translations: Arc<Translations>,
+    placeholders: Vec<(String, Expression)>,
+}
+
+impl TFilter {
+    fn lookup(&self, lang: &str, key: &str) -> Option<&String> {
+        self.translations
+            .get(lang)
+            .and_then(|strings| strings.get(key))
+    }
+}
+
+impl Filter for TFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> liquid_core::Result<Value> {
+        let key = input.to_kstr().into_string();
+        // Strings missing from a translation fall back to the default language.
+        let Some(text) = self
+            .lookup(&self.lang, &key)
+            .or_else(|| self.lookup(&self.default_language, &key))
+        else {
+            let mut available: Vec<&str> = self
+                .translations
+                .get(&self.lang)
+                .into_iter()
+                .chain(self.translations.get(&self.default_language))
+                .flat_map(|strings| strings.keys().map(String::as_str))
+                .collect();
+            available.sort_unstable();
+            available.dedup();
+            let error = liquid_core::Error::with_msg("Unknown translation")
+                .context("requested key", key.clone())
+                .context("language", self.lang.clone())
+                .context("available keys", available.join(", "));
+            return Err(error);
+        };
+
+        let mut text = text.clone();
+        for (name, value) in &self.placeholders {
+            let value = value.evaluate(runtime)?;
+            text = text.replace(&format!("{{{}}}", name), value.to_kstr().as_str());
+        }
+        Ok(Value::scalar(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    const LANGUAGES: &str =
+        "languages: {fr: {name: Français, title: Exemple, locale: fr_CA}, de: {}}";
+
+    #[test]
+    fn document_language_reads_suffixes_and_directories() {
+        let config = test_config(LANGUAGES);
+        let language = document_language(&config, "blog/hello.fr.md");
+        assert_eq!(
+            (language.lang.as_str(), language.translation_key.as_str()),
+            ("fr", "blog/hello.md")
+        );
+        let language = document_language(&config, "de/blog/hello.md");
+        assert_eq!(
+            (language.lang.as_str(), language.translation_key.as_str()),
+            ("de", "blog/hello.md")
+        );
+        let language = document_language(&config, "blog/hello.es.md");
+        assert_eq!(
+            (language.lang.as_str(), language.translation_key.as_str()),
+            ("en", "blog/hello.es.md")
+        );
+    }
+
+    #[test]
+    fn language_prefix_leaves_the_default_language_at_the_root() {
+        let config = test_config(LANGUAGES);
+        assert_eq!(language_prefix(&config, "en"), "");
+        assert_eq!(language_prefix(&config, "fr"), "/fr");
+    }
+
+    #[test]
+    fn language_config_applies_the_language_entry() {
+        let config = test_config(LANGUAGES);
+        let fr = language_config(&config, "fr");
+        assert_eq!(fr.title, "Exemple");
+        assert_eq!(fr.base_url, "https://example.org/");
+        assert_eq!(fr.seo.locale.as_deref(), Some("fr_CA"));
+        let de = language_config(&config, "de");
+        assert_eq!(de.title, "Example");
+        assert_eq!(de.seo.locale, None);
+    }
+
+    #[test]
+    fn language_config_does_not_prefix_twice() {
+        let config = test_config(
+            "base_url: https://example.org/docs/\nlanguages: {fr: {base_url: 'https://example.org/docs/fr/'}, de: {base_url: 'https://beispiel.de/'}}",
+        );
+        let fr = language_config(&config, "fr");
+        assert_eq!(fr.base_url, "https://example.org/docs/");
+        assert_eq!(fr.base_path, "/docs");
+        assert_eq!(
+            absolute_url(&fr.base_url, "/fr/about/"),
+            "https://example.org/docs/fr/about/"
+        );
+        let de = language_config(&config, "de");
+        assert_eq!(de.base_url, "https://beispiel.de/");
+        assert_eq!(de.base_path, "");
+    }
+
+    #[test]
+    fn link_translations_links_every_language() {
+        let config = test_config("languages: {fr: {base_url: 'https://example.org/fr/'}, de: {}}");
+        let mut documents = vec![
+            test_document(
+                &config,
+                "about.fr.md",
+                "/fr/about/index.html",
+                "title: À propos",
+            ),
+            test_document(&config, "about.md", "/about/index.html", "title: About"),
+            test_document(&config, "contact.md", "/contact/index.html", "{}"),
+        ];
+        link_translations(&config, &mut documents);
+
+        let hreflang: Vec<(&str, &str)> = documents[0]
+            .hreflang
+            .iter()
+            .map(|a| (a.hreflang.as_str(), a.href.as_str()))
+            .collect();
+        assert_eq!(
+            hreflang,
+            [
+                ("en", "https://example.org/about/"),
+                ("fr", "https://example.org/fr/about/"),
+                ("x-default", "https://example.org/about/"),
+            ]
+        );
+        assert_eq!(documents[1].hreflang.len(), 3);
+
+        let translation = &documents[1].translations[0];
+        assert_eq!(translation.lang, "fr");
+        assert_eq!(translation.title.as_deref(), Some("À propos"));
+        assert_eq!(translation.permalink, "/fr/about/");
+        assert!(documents[2].translations.is_empty());
+        assert!(documents[2].hreflang.is_empty());
+    }
+}
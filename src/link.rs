@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use liquid_core::error::ResultLiquidReplaceExt;
+use liquid_core::Expression;
+use liquid_core::Language;
+use liquid_core::Renderable;
+use liquid_core::Runtime;
+use liquid_core::ValueView;
+use liquid_core::{Error, Result};
+use liquid_core::{ParseTag, TagReflection, TagTokenIter};
+
+use crate::urls::relative_url;
+
+/// normalize_source_path turns a source path such as `./blog/index.md` or `/blog/index.md` into
+/// the form used to look up documents, eg `blog/index.md`.
+pub fn normalize_source_path(path: &str) -> String {
+    path.trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// LinkTag outputs the url of another source document, eg
+/// `{% link "blog/article-one/index.md" %}`, and fails when that document does not exist.
+#[derive(Clone, Debug)]
+pub struct LinkTag {
+    /// permalinks maps normalized source paths to document permalinks.
+    pub permalinks: Arc<HashMap<String, String>>,
+    pub base_path: String,
+}
+
+impl LinkTag {
+    fn resolve(&self, target: &str) -> Option<String> {
+        self.permalinks
+            .get(&normalize_source_path(target))
+            .map(|permalink| relative_url(&self.base_path, permalink))
+    }
+}
+
+impl TagReflection for LinkTag {
+    fn tag(&self) -> &'static str {
+        "link"
+    }
+
+    fn description(&self) -> &'static str {
+        "Outputs the url of another source document, failing if it does not exist."
+    }
+}
+
+impl ParseTag for LinkTag {
+    fn parse(
+        &self,
+        mut arguments: TagTokenIter<'_>,
+        _options: &Language,
+    ) -> Result<Box<dyn Renderable>> {
+        let target = arguments
+            .expect_next("Source path expected.")?
+            .expect_value()
+            .into_result()?;
+
+        // Literal targets are checked while parsing, so the error points at the calling line.
+        let link = match target {
+            Expression::Literal(value) => {
+                let target = value.to_kstr().into_string();
+                match self.resolve(&target) {
+                    Some(url) => Link::Resolved(url),
+                    None => {
+                        return Err(arguments
+                            .raise_error(&format!("link target `{}` does not exist", target)))
+                    }
+                }
+            }
+            target => Link::Deferred {
+                target,
+                tag: self.clone(),
+            },
+        };
+
+        arguments.expect_nothing()?;
+
+        Ok(Box::new(link))
+    }
+
+    fn reflection(&self) -> &dyn TagReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+enum Link {
+    Resolved(String),
+    Deferred { target: Expression, tag: LinkTag },
+}
+
+impl Renderable for Link {
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        let url = match self {
+            Link::Resolved(url) => url.clone(),
+            Link::Deferred { target, tag } => {
+                let target = target.evaluate(runtime)?.to_kstr().into_string();
+                tag.resolve(&target).ok_or_else(|| {
+                    Error::with_msg("link target does not exist").context("target", target)
+                })?
+            }
+        };
+        write!(writer, "{}", url).replace("Failed to render")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, globals: liquid::Object) -> Result<String> {
+        let mut permalinks = HashMap::new();
+        permalinks.insert("blog/index.md".to_string(), "/blog/".to_string());
+        let parser = liquid::ParserBuilder::with_stdlib()
+            .tag(LinkTag {
+                permalinks: Arc::new(permalinks),
+                base_path: "/docs".to_string(),
+            })
+            .build()
+            .unwrap();
+        parser.parse(template)?.render(&globals)
+    }
+
+    #[test]
+    fn normalize_source_path_drops_leading_dots_and_slashes() {
+        assert_eq!(normalize_source_path("./blog/index.md"), "blog/index.md");
+        assert_eq!(normalize_source_path("/blog/index.md"), "blog/index.md");
+        assert_eq!(normalize_source_path("blog/index.md"), "blog/index.md");
+    }
+
+    #[test]
+    fn link_resolves_source_paths() {
+        assert_eq!(
+            render(r#"{% link "./blog/index.md" %}"#, liquid::Object::new()).unwrap(),
+            "/docs/blog/"
+        );
+        let globals = liquid::object!({ "target": "/blog/index.md" });
+        assert_eq!(render("{% link target %}", globals).unwrap(), "/docs/blog/");
+    }
+
+    #[test]
+    fn link_fails_for_unknown_source_paths() {
+        let error = render(r#"{% link "blog/missing.md" %}"#, liquid::Object::new()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("link target `blog/missing.md` does not exist"));
+
+        let globals = liquid::object!({ "target": "blog/missing.md" });
+        let error = render("{% link target %}", globals).unwrap_err();
+        assert!(error.to_string().contains("blog/missing.md"));
+    }
+}
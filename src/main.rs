@@ -8,19 +8,43 @@ use std::{
     fs::{self, create_dir_all, File},
     io::{self, Read, Write},
     path::Path,
+    sync::Arc,
 };
 mod base_cli;
+mod check;
+mod content_source;
+mod data_pages;
+mod defaults;
+mod destinations;
+mod diagnostics;
+mod feeds;
+mod fingerprint;
+mod hierarchy;
+mod i18n;
+mod link;
+mod minify;
+mod outputs;
+mod paginate;
+mod permalink;
+mod redirects;
+mod related;
+mod search;
+mod seo;
+mod sitemap;
 mod sort;
+mod taxonomy;
+mod urls;
 mod where_glob;
 use base_cli::Commands;
 use clap::Parser;
+use diagnostics::{Diagnostic, DiagnosticContext, TemplateSource};
 use eyre::{eyre, WrapErr};
-use jotdown::AttributeKind::Id;
 use jotdown::Container::Heading;
 
 use jotdown::Event::Start;
 use notify_debouncer_mini::{new_debouncer, notify::*, DebounceEventResult};
 use std::time::Duration;
+use warp::Filter;
 
 #[derive(Clone, Debug, Serialize)]
 struct DocumentData {
@@ -32,8 +56,100 @@ struct DocumentData {
     toc: Vec<TOC>,
     frontmatter: Value,
     permalink: String,
+    // data is the record a generated page was created from, eg the term of a taxonomy page.
+    data: Value,
+    // body_line_offset is the number of lines before markdown_body in the source file, so
+    // errors can point at the right line.
+    #[serde(skip)]
+    body_line_offset: usize,
+    // origin names the record a generated document comes from in errors, eg `record 3 of
+    // plugins.yaml`. It is empty for documents read from a source file.
+    #[serde(skip)]
+    origin: String,
+    // paginator is set on each page of a paginated listing, and exposed as `paginator`.
+    #[serde(skip)]
+    paginator: Option<paginate::Paginator>,
+    // navigation holds the parent, children, siblings, previous/next pages and breadcrumbs. It is
+    // only filled in for the document being rendered.
+    #[serde(flatten)]
+    navigation: hierarchy::Navigation,
+    // lang is the language of the document, eg `fr` for `about.fr.md` or `fr/about.md`.
+    lang: String,
+    // translation_key is the source path without the language, shared by all translations.
+    #[serde(skip)]
+    translation_key: String,
+    // translations links to the document in other languages, and hreflang lists every language
+    // the document exists in, for `<link rel="alternate" hreflang="...">`.
+    translations: Vec<i18n::Translation>,
+    hreflang: Vec<i18n::Alternate>,
+    // related lists the documents sharing the most taxonomy terms and words with this one. Like
+    // navigation, it is only filled in for the document being rendered.
+    related: Vec<hierarchy::PageRef>,
 }
 
+impl DocumentData {
+    // new creates a document which is written to file_destination_path, a path relative to the
+    // public directory such as `/blog/index.html`.
+    fn new(
+        root_dir: &str,
+        file_path: String,
+        file_destination_path: &str,
+        markdown_body: String,
+        frontmatter: Value,
+        body_line_offset: usize,
+    ) -> Self {
+        let file_destination_path = file_destination_path.trim_start_matches('/');
+        let source_path = link::normalize_source_path(
+            file_path.strip_prefix(root_dir).unwrap_or(&file_path),
+        );
+
+        DocumentData {
+            file_path,
+            file_destination_path: format!("{}/{}", public_dir(root_dir), file_destination_path),
+            markdown_body: markdown_body.clone(),
+            markdown_processed: "".into(),
+            frontmatter,
+            content: markdown_body,
+            toc: vec![],
+            body_line_offset,
+            origin: String::new(),
+            data: Value::Null,
+            paginator: None,
+            navigation: hierarchy::Navigation::default(),
+            lang: String::new(),
+            translation_key: source_path,
+            translations: vec![],
+            hreflang: vec![],
+            related: vec![],
+            // The permalink is root-relative and does not include the base_path, so it stays the
+            // same whichever directory the site is built from. Use the relative_url filter to
+            // link to it.
+            permalink: format!(
+                "/{}",
+                file_destination_path.trim_end_matches("index.html")
+            ),
+        }
+    }
+
+    // source_path is the path of the source file relative to the root_dir, eg `blog/index.md`.
+    fn source_path(&self, root_dir: &str) -> String {
+        link::normalize_source_path(
+            self.file_path
+                .strip_prefix(root_dir)
+                .unwrap_or(&self.file_path),
+        )
+    }
+}
+
+// public_dir is the directory the site is generated into.
+fn public_dir(root_dir: &str) -> String {
+    Path::new(root_dir)
+        .join("_quickstatic/public")
+        .to_string_lossy()
+        .to_string()
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug, Serialize)]
 struct TOC {
     level: usize,
@@ -43,21 +159,105 @@ struct TOC {
 
 // Config struct represents a key value tree of everything in the quickstatic config file.
 // The quickstatic config file should be at: <static_site_>
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 struct Config {
     base_url: String,
     title: String,
     layouts: IndexMap<String, String>,
     ignore: Vec<String>,
+    // prefix_root_relative_urls rewrites root-relative href and src attributes in the generated
+    // html to start with the base_path, for sites deployed under a subpath.
+    #[serde(default)]
+    prefix_root_relative_urls: bool,
+    // taxonomies groups pages by frontmatter values such as tags or categories.
+    #[serde(default)]
+    taxonomies: IndexMap<String, taxonomy::TaxonomyConfig>,
+    // permalinks maps source globs to permalink patterns, eg `blog/**/*.md: /:year/:month/:slug/`.
+    #[serde(default)]
+    permalinks: IndexMap<String, String>,
+    // pretty_urls writes html documents such as `about.md` to `about/index.html`, so they are
+    // served as `/about/`.
+    #[serde(default)]
+    pretty_urls: bool,
+    // defaults applies frontmatter values to every document matching a glob.
+    #[serde(default)]
+    defaults: Vec<defaults::FrontmatterDefault>,
+    // redirects maps old urls to the urls they moved to. Documents can also list their old urls
+    // in the `aliases` frontmatter.
+    #[serde(default)]
+    redirects: IndexMap<String, String>,
+    // redirect_files selects the host specific redirect files to generate, eg netlify's
+    // `_redirects`.
+    #[serde(default)]
+    redirect_files: Vec<redirects::RedirectFile>,
+    // default_language is the language of documents without a language in their path.
+    #[serde(default = "default_language")]
+    default_language: String,
+    // languages configures multilingual sites, with the values each language overrides.
+    #[serde(default)]
+    languages: IndexMap<String, i18n::LanguageConfig>,
+    // related weighs how `this.related` documents are picked.
+    #[serde(default)]
+    related: related::RelatedConfig,
+    // data_pages generates a page per record of a data file, eg a catalogue of integrations.
+    #[serde(default)]
+    data_pages: Vec<data_pages::DataPages>,
+    // content_sources reads documents from a local SQLite database or an NDJSON file.
+    #[serde(default)]
+    content_sources: Vec<content_source::ContentSourceConfig>,
+    // feeds writes RSS, Atom and JSON Feed files for collections of documents.
+    #[serde(default)]
+    feeds: Vec<feeds::FeedConfig>,
+    // sitemap writes a `sitemap.xml` listing every html page. It is on by default.
+    #[serde(default = "default_sitemap")]
+    sitemap: bool,
+    // robots configures the generated `robots.txt`.
+    #[serde(default)]
+    robots: sitemap::RobotsConfig,
+    // output_formats declares extra files rendered next to the html of a document, eg json, and
+    // outputs maps source globs to the formats their documents get.
+    #[serde(default)]
+    output_formats: IndexMap<String, outputs::OutputFormat>,
+    #[serde(default)]
+    outputs: IndexMap<String, Vec<String>>,
+    // minify minifies the generated html and the copied css and js, in the environments it lists.
+    #[serde(default)]
+    minify: Option<minify::MinifyConfig>,
+    // search configures the pagefind search index.
+    #[serde(default)]
+    search: search::SearchConfig,
+    // seo holds the site wide description, author, image and locale the seo tag falls back to.
+    #[serde(default)]
+    seo: seo::SeoConfig,
+    // fingerprint adds a hash of their content to the names of the copied files it matches.
+    #[serde(default)]
+    fingerprint: Option<fingerprint::FingerprintConfig>,
+    // base_path is the path component of the base_url, eg `/docs` for `https://example.org/docs/`.
+    #[serde(skip_deserializing)]
+    base_path: String,
+    // environment is what the site is built for, eg production or development, from the --env
+    // flag.
+    #[serde(skip_deserializing)]
+    environment: String,
     #[serde(skip_deserializing)]
     raw: Value,
 }
 
+fn default_language() -> String {
+    "en".to_string()
+}
+
+fn default_sitemap() -> bool {
+    true
+}
+
 #[derive(Debug, Serialize)]
 struct RenderContext<'a> {
     config: &'a Config,
     this: &'a mut DocumentData,
     file_list: Vec<DocumentData>,
+    taxonomies: &'a taxonomy::Taxonomies,
+    paginator: Option<paginate::Paginator>,
 }
 
 fn get_file_paths_recursive(
@@ -75,7 +275,7 @@ fn get_file_paths_recursive(
                 if config_struct
                     .ignore
                     .iter()
-                    .find(|glb| glob_match::glob_match(&glb, &path.to_str().unwrap()))
+                    .find(|glb| glob_match::glob_match(glb, path.to_str().unwrap()))
                     .is_some()
                 {
                     continue;
@@ -118,6 +318,7 @@ fn copy_recursive(
     src: &Path,
     exclude_dir_names: &Vec<&str>,
     dest: &Path,
+    assets: &mut fingerprint::Assets,
 ) -> eyre::Result<()> {
     if src.is_dir() {
         if !dest.exists() {
@@ -133,7 +334,7 @@ fn copy_recursive(
             if config_struct
                 .ignore
                 .iter()
-                .find(|glb| glob_match::glob_match(&glb, &path.to_str().unwrap()))
+                .find(|glb| glob_match::glob_match(glb, path.to_str().unwrap()))
                 .is_some()
             {
                 continue;
@@ -148,19 +349,38 @@ fn copy_recursive(
                 if exclude_dir_names.contains(&file_name.as_str()) || file_name.starts_with(".") {
                     continue; // Skip this directory and continue with the next entry
                 }
-                copy_recursive(config_struct, &path, exclude_dir_names, &new_dest)?;
+                copy_recursive(config_struct, &path, exclude_dir_names, &new_dest, assets)?;
             } else {
                 if !path
                     .file_name()
-                    .expect(&format!("copy_recursive: failed to get file name {path:?}"))
+                    .unwrap_or_else(|| panic!("copy_recursive: failed to get file name {path:?}"))
                     .to_str()
-                    .expect(&format!(
-                        "copy_recursive: failed converting os string to string for path {path:?}"
-                    ))
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "copy_recursive: failed converting os string to string for path {path:?}"
+                        )
+                    })
                     .ends_with(".md")
                 {
-                    fs::copy(&path, &new_dest)
-                        .wrap_err(format!("copy_recursive: failed to copy {path:?}"))?;
+                    let minified = minify::minify_asset(config_struct, &path)?;
+                    if assets.fingerprints(&path) {
+                        let contents = match minified {
+                            Some(minified) => minified.into_bytes(),
+                            None => fs::read(&path)?,
+                        };
+                        assets.write(&path, &new_dest, &contents)?;
+                        continue;
+                    }
+                    match minified {
+                        Some(minified) => write_to_location(
+                            new_dest.to_string_lossy().to_string(),
+                            minified.as_bytes(),
+                        )?,
+                        None => {
+                            fs::copy(&path, &new_dest)
+                                .wrap_err(format!("copy_recursive: failed to copy {path:?}"))?;
+                        }
+                    }
                 }
             }
         }
@@ -180,67 +400,55 @@ async fn main() -> eyre::Result<()> {
     let cli_instance = base_cli::Cli::parse();
 
     match cli_instance.command {
-        None => build_with_index(cli_instance.dir).await,
-        Some(Commands::Build {}) => build_with_index(cli_instance.dir).await,
-        Some(Commands::Serve { port }) => serve(cli_instance.dir, port).await,
+        None => {
+            let environment = cli_instance.env.unwrap_or_else(|| "production".to_string());
+            build_with_index(cli_instance.dir, &environment, false).await
+        }
+        Some(Commands::Build { check }) => {
+            let environment = cli_instance.env.unwrap_or_else(|| "production".to_string());
+            build_with_index(cli_instance.dir, &environment, check).await
+        }
+        Some(Commands::Check {}) => {
+            let environment = cli_instance.env.unwrap_or_else(|| "production".to_string());
+            let documents = build(cli_instance.dir.clone(), &environment)?;
+            check_site(&cli_instance.dir, &documents)
+        }
+        Some(Commands::Serve { port }) => {
+            let environment = cli_instance
+                .env
+                .unwrap_or_else(|| "development".to_string());
+            serve(cli_instance.dir, port, environment).await
+        }
     }
 }
 
-async fn build_with_index(root_dir: String) -> eyre::Result<()> {
-    build(root_dir)?;
+async fn build_with_index(root_dir: String, environment: &str, check: bool) -> eyre::Result<()> {
+    let documents = build(root_dir.clone(), environment)?;
 
     // Generate pagefind's search index
-    // let options = pagefind::SearchOptions {
-    // let options = pagefind::PagefindInboundConfig{
-    //     source: "_quickstatic/public/".to_string(),
-    //     site:  "_quickstatic/public/".to_string(),
-    //     bundle_dir: None,
-    //     output_subdir: None,
-    //     output_path: None,
-    //     root_selector: "html".into(),
-    //     exclude_selectors: vec![],
-    //     glob: "**/*.{html}".into(),
-    //     force_language: None,
-    //     serve: false,
-    //     verbose: true,
-    //     logfile: None,
-    //     keep_index_url: false,
-    //     service: false,
-    // };
-    // let search_options = pagefind::SearchOptions::load(options).unwrap();
-    // let runner = &mut pagefind::SearchState::new(search_options.clone());
-
-    let options = pagefind::options::PagefindServiceConfig::builder()
-        .keep_index_url(true)
-        .force_language("en".to_string())
-        .build();
-    let mut runner =
-        pagefind::api::PagefindIndex::new(Some(options)).expect("Options should be valid");
-    runner.add_directory(
-        "_quickstatic/public/".to_string(),
-        Some("**/*.{html}".into()),
-    );
-
-    // runner.log_start();
-    // _ = runner
-    //     .fossick_many(search_options.site_source.clone(), search_options.glob.clone())
-    //     .await;
-
-    runner.build_indexes().await;
+    let config = load_config(&root_dir)?;
+    search::build_index(&root_dir, &config).await?;
 
+    // Links are checked once the search index exists, as layouts may link to its files.
+    if check {
+        check_site(&root_dir, &documents)?;
+    }
     Ok(())
 }
 
-async fn serve(dir: String, http_port: u16) -> eyre::Result<()> {
+async fn serve(dir: String, http_port: u16, environment: String) -> eyre::Result<()> {
+    let base_path = load_config(&dir)?.base_path;
     println!(
-        "Serving quickstatic at: https://localhost:{} and directory: {}\n\n",
-        http_port, dir
+        "Serving quickstatic at: http://localhost:{}{}/ and directory: {}\n\n",
+        http_port, base_path, dir
     );
 
-    // Run the directory watcher in a separate thread
+    // Run the directory watcher in a separate thread. The search index is rebuilt in the
+    // background after every build.
     let dir_clone = dir.clone();
+    let indexer = search::BackgroundIndexer::new(&dir);
     std::thread::spawn(move || {
-        match watch_directory_and_run_command(&dir_clone)
+        match watch_directory_and_run_command(&dir_clone, &environment, &indexer)
             .wrap_err("watch_directory_and_run_command error")
         {
             Err(e) => println!("Build Error: {:?}\n", e),
@@ -249,15 +457,39 @@ async fn serve(dir: String, http_port: u16) -> eyre::Result<()> {
     });
 
     let dir_to_serve = dir + "/_quickstatic/public/";
-    let _ = serve_directory(http_port, dir_to_serve).await;
+    let _ = serve_directory(http_port, dir_to_serve, base_path).await;
     Ok(())
 }
 
-fn watch_directory_and_run_command(dir: &str) -> eyre::Result<()> {
-    match build(dir.to_string()) {
+// check_site reports every broken internal link and anchor of the generated site, and fails when
+// there are any.
+fn check_site(root_dir: &str, documents: &[DocumentData]) -> eyre::Result<()> {
+    let config = load_config(root_dir)?;
+    let broken = check::check_links(root_dir, &config, documents)?;
+    for link in &broken {
+        eprintln!("{}", link);
+    }
+    if broken.is_empty() {
+        println!("No broken links found");
+        Ok(())
+    } else {
+        Err(eyre!("found {} broken links", broken.len()))
+    }
+}
+
+fn watch_directory_and_run_command(
+    dir: &str,
+    environment: &str,
+    indexer: &search::BackgroundIndexer,
+) -> eyre::Result<()> {
+    let rebuild = || match build(dir.to_string(), environment) {
         Err(e) => println!("Build Error: {:?}\n", e),
-        Ok(_res) => println!("Rebuilt site \n"),
+        Ok(_res) => {
+            println!("Rebuilt site \n");
+            indexer.refresh();
+        }
     };
+    rebuild();
 
     let (tx, rx) = std::sync::mpsc::channel();
 
@@ -281,10 +513,7 @@ fn watch_directory_and_run_command(dir: &str) -> eyre::Result<()> {
             Ok(events) => events
                 .iter()
                 .filter(|f| !f.path.to_str().unwrap().contains("_quickstatic/public/"))
-                .for_each(|_| match build(dir.to_string()) {
-                    Err(e) => println!("Build Error: {:?}\n", e),
-                    Ok(_res) => println!("Rebuilt site \n"),
-                }),
+                .for_each(|_| rebuild()),
             Err(e) => println!("Error {:?}", e),
         }
     }
@@ -292,15 +521,23 @@ fn watch_directory_and_run_command(dir: &str) -> eyre::Result<()> {
     Ok(())
 }
 
-async fn serve_directory(port: u16, dir: String) -> eyre::Result<()> {
-    let route = warp::fs::dir(dir);
+async fn serve_directory(port: u16, dir: String, base_path: String) -> eyre::Result<()> {
+    // Serve the site under the same path it would be deployed to, so urls prefixed with the
+    // base_path resolve locally.
+    let prefix = base_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .fold(warp::any().boxed(), |prefix, segment| {
+            prefix.and(warp::path(segment.to_string())).boxed()
+        });
+    let route = prefix.and(warp::fs::dir(dir));
     warp::serve(route).run(([127, 0, 0, 1], port)).await;
     Ok(())
 }
 
 fn find_template(layouts_map: IndexMap<String, String>, file_path: String) -> eyre::Result<String> {
     for (k, v) in &layouts_map {
-        if glob_match::glob_match(&k, &file_path.clone()) {
+        if glob_match::glob_match(k, &file_path.clone()) {
             return Ok(v.into());
         };
     }
@@ -308,24 +545,80 @@ fn find_template(layouts_map: IndexMap<String, String>, file_path: String) -> ey
     Err(eyre::eyre!("expecting a general layout glob such as **/*.md to be set in the ./quickstatic.yaml config file: {:?} layout_map: {:?}", file_path, layouts_map))
 }
 
-fn build(root_dir: String) -> eyre::Result<()> {
-    let dir = Path::new(&root_dir); // Specify the directory
-
-    // Read the config into a config struct.
-    let config_file_content = fs::read_to_string(dir.join("quickstatic.yaml"))
+// load_config reads the quickstatic.yaml in the root_dir into a config struct.
+fn load_config(root_dir: &str) -> eyre::Result<Config> {
+    let config_file_content = fs::read_to_string(Path::new(root_dir).join("quickstatic.yaml"))
         .wrap_err("unable to find `quickstatic.yaml` config file")?;
     let config_value = serde_yaml::from_str::<Value>(&config_file_content)
         .wrap_err("unable to unmarshal config into serde yaml Value")?;
     let mut config_struct: Config = serde_yaml::from_value(config_value.clone())?;
+    config_struct.base_path = urls::base_path_from_url(&config_struct.base_url);
     config_struct.raw = config_value;
+    Ok(config_struct)
+}
+
+// test_config parses a config the way load_config does, for unit tests. Keys the test leaves out
+// get the values of a minimal site.
+#[cfg(test)]
+fn test_config(yaml: &str) -> Config {
+    let mut config_value: Value = serde_yaml::from_str(
+        "base_url: https://example.org/\ntitle: Example\nlayouts: {}\nignore: []",
+    )
+    .unwrap();
+    if let (Some(config), Value::Mapping(overrides)) = (
+        config_value.as_mapping_mut(),
+        serde_yaml::from_str::<Value>(yaml).unwrap(),
+    ) {
+        config.extend(overrides);
+    }
+    let mut config_struct: Config = serde_yaml::from_value(config_value.clone()).unwrap();
+    config_struct.base_path = urls::base_path_from_url(&config_struct.base_url);
+    config_struct.raw = config_value;
+    config_struct
+}
+
+// test_document creates a document of the /site root_dir as the build reads it, with the
+// language of its source path.
+#[cfg(test)]
+fn test_document(
+    config: &Config,
+    source_path: &str,
+    file_destination_path: &str,
+    frontmatter: &str,
+) -> DocumentData {
+    let mut document = DocumentData::new(
+        "/site",
+        format!("/site/{}", source_path),
+        file_destination_path,
+        String::new(),
+        serde_yaml::from_str(frontmatter).unwrap(),
+        0,
+    );
+    let language = i18n::document_language(config, source_path);
+    document.lang = language.lang;
+    document.translation_key = language.translation_key;
+    document
+}
+
+// build generates the site, and returns its documents.
+fn build(root_dir: String, environment: &str) -> eyre::Result<Vec<DocumentData>> {
+    let dir = Path::new(&root_dir); // Specify the directory
+
+    // Read the config into a config struct.
+    let mut config_struct = load_config(&root_dir)?;
+    config_struct.environment = environment.to_string();
+    let minify_config = minify::active(&config_struct).cloned();
 
     let exclude_dir_names = vec!["_quickstatic", ".git", "node_modules"];
+    let mut assets = fingerprint::Assets::new(&root_dir, config_struct.fingerprint.clone());
     copy_recursive(
         &config_struct,
         dir,
         &exclude_dir_names,
         Path::new(&format!("{root_dir}/_quickstatic/public/")),
+        &mut assets,
     )?;
+    assets.write_manifest(&public_dir(&root_dir))?;
     let file_paths = get_file_paths_recursive(
         &config_struct,
         dir,
@@ -347,34 +640,6 @@ fn build(root_dir: String) -> eyre::Result<()> {
     //
     // key and key2 are keys in the frontmatter associated with the markdown above.
     let matter = Matter::<YAML>::new();
-    let themes_dir = format!("{root_dir}/_quickstatic/themes");
-    create_dir_all(&themes_dir)?;
-    let liquid_source_map = read_partials_from_directory(Path::new(&themes_dir), "liquid")?;
-
-    let mut liquid_mem_source = InMemorySource::new();
-    for (fp, src) in liquid_source_map {
-        liquid_mem_source.add(fp, src);
-    }
-
-    let partials_compiler = EagerCompiler::new(liquid_mem_source);
-    // TODO: do this in a new loop, so the context can contain the entire render tree, to
-    // support referencing other documents in the template. Eg in table of content pages.
-    // or listing categories and tags.
-    let builder = liquid::ParserBuilder::with_stdlib()
-        .filter(crate::where_glob::WhereGlob)
-        .filter(crate::where_glob::Ternary)
-        .filter(crate::where_glob::StartsWith)
-        .filter(crate::where_glob::Equals)
-        .filter(crate::where_glob::Markdownify)
-        .filter(crate::sort::Sort)
-        .filter(liquid_lib::jekyll::Slugify)
-        .filter(liquid_lib::jekyll::Push)
-        .filter(liquid_lib::jekyll::Pop)
-        .filter(liquid_lib::jekyll::Unshift)
-        .filter(liquid_lib::jekyll::Shift)
-        .filter(liquid_lib::jekyll::ArrayToSentenceString)
-        .filter(liquid_lib::shopify::Pluralize);
-    let parser_builder = builder.partials(partials_compiler).build()?;
 
     // documents_map holds the entire documents tree and can be iterated eg via prefix.
     let mut documents_list = vec![];
@@ -384,85 +649,182 @@ fn build(root_dir: String) -> eyre::Result<()> {
         let file_path_no_root = file_path_str.strip_prefix(&root_dir).unwrap();
         let contents = fs::read_to_string(file_path.clone())?;
 
+        let source_path = link::normalize_source_path(file_path_no_root);
+        let language = i18n::document_language(&config_struct, &source_path);
+
         // Any files with the liquid extension are stripped of the .liquid. The content is treated
         // as main content as is.
-        let (file_content, frontmatter, file_destination_path) =
-            if file_path_no_root.ends_with(".md") {
-                let result = matter.parse(&contents);
-                let frontmatter: Value = result
-                    .data
-                    .unwrap_or(gray_matter::Pod::Null)
-                    .clone()
-                    .deserialize()?;
-
-                (
-                    result.content.clone(),
-                    frontmatter,
-                    file_path_no_root.strip_suffix(".md").unwrap().to_owned() + ".html",
-                )
-            } else {
-                (
-                    contents,
-                    serde_yaml::Value::Null,
-                    file_path_no_root
-                        .strip_suffix(".liquid")
-                        .unwrap()
-                        .to_owned(),
-                )
-            };
-        let public_root_path = Path::new(&root_dir)
-            .join("_quickstatic/public")
-            .to_string_lossy()
-            .to_owned()
-            .to_string();
-        let final_file_destination_path = format!("{}{}", public_root_path, file_destination_path);
-
-        let document = DocumentData {
-            file_path: file_path.clone(),
-            file_destination_path: final_file_destination_path.clone(),
-            markdown_body: file_content.clone(),
-            markdown_processed: "".into(),
-            frontmatter,
-            content: file_content.into(),
-            toc: vec![],
-            permalink: final_file_destination_path
+        let (file_content, frontmatter, body_line_offset) = if file_path_no_root.ends_with(".md") {
+            let result = matter.parse(&contents);
+            let frontmatter: Value = result
+                .data
+                .unwrap_or(gray_matter::Pod::Null)
                 .clone()
-                .to_string()
-                .trim_end_matches("index.html")
-                .trim_start_matches("./_quickstatic/public")
+                .deserialize()?;
+
+            (
+                result.content.clone(),
+                frontmatter,
+                diagnostics::frontmatter_line_offset(&contents),
+            )
+        } else {
+            (contents, serde_yaml::Value::Null, 0)
+        };
+        // Translations are generated at the same path as the original, under the prefix of their
+        // language, eg `about.fr.md` and `fr/about.md` both become `/fr/about.html`.
+        let file_destination_path = match language.translation_key.strip_suffix(".md") {
+            Some(stem) => stem.to_owned() + ".html",
+            None => language
+                .translation_key
+                .strip_suffix(".liquid")
+                .unwrap()
                 .to_owned(),
         };
+        let frontmatter = defaults::apply(&config_struct.defaults, &source_path, frontmatter);
+        let file_destination_path = i18n::language_prefix(&config_struct, &language.lang)
+            + &permalink::destination(
+                &config_struct,
+                &language.translation_key,
+                &file_destination_path,
+                &frontmatter,
+                &Value::Null,
+            )?;
+        let mut document = DocumentData::new(
+            &root_dir,
+            file_path.clone(),
+            &file_destination_path,
+            file_content,
+            frontmatter,
+            body_line_offset,
+        );
+        document.lang = language.lang;
+        document.translation_key = language.translation_key;
 
         documents_list.push(document);
     }
+    documents_list.extend(data_pages::data_pages(&root_dir, &config_struct)?);
+    documents_list.extend(content_source::content_source_documents(
+        &root_dir,
+        &config_struct,
+    )?);
+    i18n::link_translations(&config_struct, &mut documents_list);
+
+    // taxonomies holds the pages for every term of every language, eg every tag, and generates a
+    // page per term.
+    let taxonomies = taxonomy::collect_taxonomies(&config_struct, &documents_list);
+    let taxonomy_pages =
+        taxonomy::taxonomy_pages(&root_dir, &config_struct, &taxonomies, &documents_list)?;
+    documents_list.extend(taxonomy_pages);
+
+    // permalinks maps each source path, relative to the root_dir, to the permalink of its
+    // document. It backs the link tag.
+    let permalinks: HashMap<String, String> = documents_list
+        .iter()
+        .map(|document: &DocumentData| {
+            (document.source_path(&root_dir), document.permalink.clone())
+        })
+        .collect();
+
+    let themes_dir = format!("{root_dir}/_quickstatic/themes");
+    create_dir_all(&themes_dir)?;
+    let liquid_source_map = read_partials_from_directory(Path::new(&themes_dir), "liquid")?;
+
+    // Every language gets its own parser, as filters such as t and absolute_url depend on it.
+    let permalinks = Arc::new(permalinks);
+    let translations = Arc::new(i18n::read_translations(&themes_dir)?);
+    let asset_manifest = Arc::new(assets.manifest);
+    let mut parsers = HashMap::new();
+    for lang in std::iter::once(&config_struct.default_language).chain(config_struct.languages.keys())
+    {
+        if parsers.contains_key(lang) {
+            continue;
+        }
+        let language_config = i18n::language_config(&config_struct, lang);
+        let parser = build_parser(
+            &language_config,
+            lang,
+            &liquid_source_map,
+            permalinks.clone(),
+            translations.clone(),
+            asset_manifest.clone(),
+        )?;
+        parsers.insert(lang.clone(), (language_config, parser));
+    }
 
     // Render all the markdowns and save them to final destination.
     let documents_list_clone = documents_list.clone();
+    // Listings with a paginate block are rendered once per page, but appear once in file_list.
+    let mut documents_list = paginate::paginate(&root_dir, documents_list, &documents_list_clone)?;
+    // Every page has a destination of its own, including those of paginated listings.
+    destinations::check_collisions(&root_dir, &documents_list)?;
+    redirects::write_redirects(&root_dir, &config_struct, &documents_list)?;
+    let hierarchy = hierarchy::Hierarchy::new(&root_dir, &documents_list_clone)?;
+    let related = related::related_documents(
+        &config_struct.related,
+        &taxonomies,
+        &documents_list_clone,
+    );
+    let no_taxonomies = taxonomy::Taxonomies::new();
     for document in &mut documents_list {
+        document.navigation = hierarchy.navigation(&document.source_path(&root_dir));
+        document.related = related.get(&document.file_path).cloned().unwrap_or_default();
+        let paginator = document.paginator.take();
+        let (language_config, parser_builder) = parsers.get(&document.lang).ok_or_else(|| {
+            eyre!(
+                "{} is in the language `{}`, which is not configured",
+                document.file_path,
+                document.lang
+            )
+        })?;
+        let language_taxonomies = taxonomies.get(&document.lang).unwrap_or(&no_taxonomies);
         let render_ctx = &mut RenderContext {
-            config: &config_struct,
+            config: language_config,
             this: document,
             file_list: documents_list_clone.clone(),
+            taxonomies: language_taxonomies,
+            paginator,
+        };
+
+        let document_path = render_ctx.this.file_path.clone();
+        let markdown_body = render_ctx.this.markdown_body.clone();
+        let body_line_offset = render_ctx.this.body_line_offset;
+        let diagnostic_ctx = DiagnosticContext {
+            document: &document_path,
+            themes_dir: &themes_dir,
+            partials: &liquid_source_map,
+        };
+        let body_error = |e: liquid::Error| {
+            diagnostics::template_error(
+                &e,
+                TemplateSource {
+                    name: document_path.clone(),
+                    text: &markdown_body,
+                    line_offset: body_line_offset,
+                },
+                &diagnostic_ctx,
+            )
         };
 
         let render_ctx_obj = liquid::to_object(&render_ctx)?;
-        if render_ctx.this.file_path.clone().ends_with(".md") {
-            let template = parser_builder
-                .parse(&render_ctx.this.markdown_body)
-                .wrap_err(format!("parser_builder.parse template.render failed on current_file: {} post-frontmatter content: {:?}",render_ctx.this.file_path, &render_ctx.this.markdown_body))?;
-
-            render_ctx.this.markdown_processed = template.render(&render_ctx_obj)?;
-            let (md_processed, toc) = process_markdown(render_ctx.this.markdown_processed.clone())
-                .wrap_err(format!("process_markdown: template.render failed on current_file: {} post-frontmatter content: {:?}",render_ctx.this.file_path, &render_ctx.this.markdown_body))?;
+        if document_path.ends_with(".md") {
+            let template = parser_builder.parse(&markdown_body).map_err(&body_error)?;
+
+            render_ctx.this.markdown_processed =
+                template.render(&render_ctx_obj).map_err(&body_error)?;
+            let (md_processed, toc) = process_markdown(
+                render_ctx.this.markdown_processed.clone(),
+                minify_config.as_ref().is_some_and(|minify| minify.html),
+            )
+            .wrap_err(format!(
+                "process_markdown failed on current_file: {}",
+                document_path
+            ))?;
             render_ctx.this.content = md_processed;
             render_ctx.this.toc = toc;
         } else {
-            let template = parser_builder
-                .parse(&render_ctx.this.content)
-                .wrap_err(format!("parser_builder.parse failed on content result of current_file: {} post-frontmatter content: {:?}",render_ctx.this.file_path, &render_ctx.this.markdown_body))?;
+            let template = parser_builder.parse(&markdown_body).map_err(&body_error)?;
 
-            render_ctx.this.content = template.render(&render_ctx_obj)
-                .wrap_err(format!("parser_builder.parse template.render failed on current_file: {} post-frontmatter content: {:?}",render_ctx.this.file_path, &render_ctx.this.markdown_body))? ;
+            render_ctx.this.content = template.render(&render_ctx_obj).map_err(&body_error)?;
         }
 
         let layout_for_document = if let Some(layout_in_cfg) = render_ctx
@@ -480,29 +842,162 @@ fn build(root_dir: String) -> eyre::Result<()> {
             )?
         };
 
+        let render_layout =
+            |layout: &str, render_ctx_obj: &liquid::Object| -> eyre::Result<String> {
+                let layout_path = Path::new(&themes_dir).join(layout);
+                let layout_source = fs::read_to_string(&layout_path).map_err(|e| Diagnostic {
+                    message: format!("unable to read layout `{}`: {}", layout, e),
+                    file: document_path.clone(),
+                    line: None,
+                    column: None,
+                    snippet: None,
+                    details: vec![],
+                    chain: vec![],
+                    suggestion: diagnostics::did_you_mean(
+                        layout,
+                        liquid_source_map.keys().map(String::as_str),
+                    ),
+                })?;
+                let rendered = parser_builder
+                    .parse(&layout_source)
+                    .and_then(|f| f.render(render_ctx_obj))
+                    .map_err(|e| {
+                        diagnostics::template_error(
+                            &e,
+                            TemplateSource {
+                                name: layout_path.to_string_lossy().to_string(),
+                                text: &layout_source,
+                                line_offset: 0,
+                            },
+                            &diagnostic_ctx,
+                        )
+                    })?;
+                Ok(rendered)
+            };
+
         let render_ctx_obj = liquid::to_object(&render_ctx)?;
-        let document_as_html = parser_builder
-            .parse_file(
-                Path::new(&root_dir)
-                    .join("_quickstatic/themes")
-                    .join(&layout_for_document),
+        let document_as_html = render_layout(&layout_for_document, &render_ctx_obj)?;
+        // The raw html markers are only meant for the minifier, which runs on the page. Feeds,
+        // outputs and the search index get the content without them.
+        if minify_config.as_ref().is_some_and(|minify| minify.html) {
+            render_ctx.this.content = minify::strip_raw_markers(&render_ctx.this.content);
+        }
+
+        let document_as_html = if config_struct.prefix_root_relative_urls
+            && render_ctx.this.file_destination_path.ends_with(".html")
+        {
+            urls::prefix_root_relative_urls(&document_as_html, &language_config.base_path)
+        } else {
+            document_as_html
+        };
+        let document_as_html = if render_ctx.this.file_destination_path.ends_with(".html") {
+            search::annotate(
+                &config_struct.search,
+                &render_ctx.this.frontmatter,
+                &document_as_html,
             )
-            .and_then(|f| f.render(&render_ctx_obj))
-            .wrap_err(format!(
-                "document_as_html failed for file_path {:?} and layout {:?}",
-                render_ctx.this.file_path, layout_for_document
-            ))?;
+            .wrap_err(format!("error in {}", render_ctx.this.file_path))?
+        } else {
+            document_as_html
+        };
+        let document_as_html = match &minify_config {
+            Some(minify)
+                if minify.html && render_ctx.this.file_destination_path.ends_with(".html") =>
+            {
+                minify::html(minify, &document_as_html)
+            }
+            _ => document_as_html,
+        };
 
         write_to_location(
             render_ctx.this.file_destination_path.to_owned(),
             document_as_html.as_bytes(),
         )?;
+
+        // Extra outputs, eg `index.json` next to `index.html`, share the rendered content.
+        let source_path = render_ctx.this.source_path(&root_dir);
+        for output in outputs::document_outputs(&config_struct, &source_path, render_ctx.this)? {
+            let rendered = match &output.layout {
+                Some(layout) => render_layout(layout, &render_ctx_obj)?,
+                None => outputs::render_builtin(&output.name, render_ctx.this)?,
+            };
+            write_to_location(output.destination, rendered.as_bytes())?;
+        }
     }
+    // Feeds hold the rendered content, so they are written last.
+    feeds::write_feeds(&root_dir, &config_struct, &taxonomies, &documents_list)?;
+    sitemap::write_sitemap(&root_dir, &config_struct, &documents_list)?;
 
-    Ok(())
+    Ok(documents_list)
+}
+
+// build_parser creates the liquid parser for documents in a language, with the partials from
+// the themes directory and quickstatic's filters and tags.
+fn build_parser(
+    config: &Config,
+    lang: &str,
+    partials: &HashMap<String, String>,
+    permalinks: Arc<HashMap<String, String>>,
+    translations: Arc<i18n::Translations>,
+    asset_manifest: Arc<IndexMap<String, fingerprint::Asset>>,
+) -> eyre::Result<liquid::Parser> {
+    let mut liquid_mem_source = InMemorySource::new();
+    for (fp, src) in partials {
+        liquid_mem_source.add(fp, src);
+    }
+
+    let partials_compiler = EagerCompiler::new(liquid_mem_source);
+    // TODO: do this in a new loop, so the context can contain the entire render tree, to
+    // support referencing other documents in the template. Eg in table of content pages.
+    // or listing categories and tags.
+    let builder = liquid::ParserBuilder::with_stdlib()
+        .filter(crate::where_glob::WhereGlob)
+        .filter(crate::where_glob::Ternary)
+        .filter(crate::where_glob::StartsWith)
+        .filter(crate::where_glob::Equals)
+        .filter(crate::where_glob::Markdownify)
+        .filter(crate::sort::Sort)
+        .filter(crate::urls::AbsoluteUrl {
+            base_url: config.base_url.clone(),
+        })
+        .filter(crate::urls::RelativeUrl {
+            base_path: config.base_path.clone(),
+        })
+        .filter(crate::fingerprint::AssetUrl {
+            manifest: asset_manifest.clone(),
+            base_path: config.base_path.clone(),
+        })
+        .filter(crate::fingerprint::Integrity {
+            manifest: asset_manifest,
+        })
+        .filter(crate::i18n::T {
+            lang: lang.to_string(),
+            default_language: config.default_language.clone(),
+            translations,
+        })
+        .filter(liquid_lib::jekyll::Slugify)
+        .filter(liquid_lib::jekyll::Push)
+        .filter(liquid_lib::jekyll::Pop)
+        .filter(liquid_lib::jekyll::Unshift)
+        .filter(liquid_lib::jekyll::Shift)
+        .filter(liquid_lib::jekyll::ArrayToSentenceString)
+        .filter(liquid_lib::shopify::Pluralize)
+        .tag(crate::link::LinkTag {
+            permalinks,
+            base_path: config.base_path.clone(),
+        })
+        .tag(crate::seo::SeoTag {
+            site_title: config.title.clone(),
+            base_url: config.base_url.clone(),
+            lang: lang.to_string(),
+            seo: config.seo.clone(),
+        });
+    Ok(builder.partials(partials_compiler).build()?)
 }
 
-fn process_markdown(md: String) -> eyre::Result<(String, Vec<TOC>)> {
+// process_markdown renders djot into html and its table of contents. With mark_raw, raw html
+// blocks are marked so the html minifier keeps them as they are.
+fn process_markdown(md: String, mark_raw: bool) -> eyre::Result<(String, Vec<TOC>)> {
     // let (remaining_input, ast) = djotters::parse_markdown(&md)
     //     .map_err(|e| eyre!("{:#}", e).wrap_err("Failed to parse markdown"))?;
 
@@ -519,7 +1014,7 @@ fn process_markdown(md: String) -> eyre::Result<(String, Vec<TOC>)> {
             Start(
                 Heading {
                     level,
-                    has_section,
+                    has_section: _,
                     id,
                 },
                 _,
@@ -538,7 +1033,11 @@ fn process_markdown(md: String) -> eyre::Result<(String, Vec<TOC>)> {
             _ => None,
         })
         .collect();
-    let html = jotdown::html::render_to_string(events);
+    let html = if mark_raw {
+        jotdown::html::render_to_string(minify::mark_raw_html(events))
+    } else {
+        jotdown::html::render_to_string(events)
+    };
     Ok((html, hds))
 }
 
@@ -581,7 +1080,7 @@ fn read_directory(
         if path.is_dir() {
             let new_prefix = format!("{}{}/", prefix, path.file_name().unwrap().to_string_lossy());
             read_directory(&path, partials, &new_prefix, extension)?;
-        } else if path.extension().map_or(false, |ext| ext == extension) {
+        } else if path.extension().is_some_and(|ext| ext == extension) {
             let partial_name = format!(
                 "{}{}.{}",
                 prefix,
@@ -0,0 +1,241 @@
+use std::fs;
+use std::path::Path;
+
+use jotdown::{Container, Event};
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+
+/// RAW_START and RAW_END surround raw html from djot's `{=html}` blocks, so the minifier leaves
+/// it as it was written. The markers are removed by the minifier, or by strip_raw_markers for
+/// content used outside the page.
+const RAW_START: &str = "<!--quickstatic:raw-->";
+const RAW_END: &str = "<!--/quickstatic:raw-->";
+
+/// MinifyConfig is the `minify:` config section. Each kind of file can be turned off, and
+/// minification only runs in the listed environments, eg
+///
+/// ``` yaml
+/// minify:
+///   js: false
+///   environments: [production, staging]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct MinifyConfig {
+    /// html minifies rendered html, including inline `<style>` and `<script>` when css and js
+    /// are on.
+    pub html: bool,
+    /// css minifies copied `.css` files.
+    pub css: bool,
+    /// js minifies copied `.js` files.
+    pub js: bool,
+    pub environments: Vec<String>,
+}
+
+impl Default for MinifyConfig {
+    fn default() -> Self {
+        MinifyConfig {
+            html: true,
+            css: true,
+            js: true,
+            environments: vec!["production".to_string()],
+        }
+    }
+}
+
+/// active returns the minify config when minification runs in the environment being built.
+pub fn active(config: &Config) -> Option<&MinifyConfig> {
+    config
+        .minify
+        .as_ref()
+        .filter(|minify| minify.environments.contains(&config.environment))
+}
+
+/// mark_raw_html surrounds the raw html of djot documents with markers, so the minifier keeps it
+/// as it is.
+pub fn mark_raw_html<'s>(
+    events: impl Iterator<Item = Event<'s>>,
+) -> impl Iterator<Item = Event<'s>> {
+    let is_raw_html = |container: &Container| {
+        matches!(
+            container,
+            Container::RawBlock { format: "html" } | Container::RawInline { format: "html" }
+        )
+    };
+    events.flat_map(move |event| match event {
+        Event::Start(container, attributes) if is_raw_html(&container) => vec![
+            Event::Start(container, attributes),
+            Event::Str(RAW_START.into()),
+        ],
+        Event::End(container) if is_raw_html(&container) => {
+            vec![Event::Str(RAW_END.into()), Event::End(container)]
+        }
+        event => vec![event],
+    })
+}
+
+/// strip_raw_markers removes the markers of mark_raw_html, leaving the html as djot renders it.
+pub fn strip_raw_markers(html: &str) -> String {
+    html.replace(RAW_START, "").replace(RAW_END, "")
+}
+
+pub fn css(source: &str) -> String {
+    // Stylesheets the minifier can not parse are kept as they are.
+    match minifier::css::minify(source) {
+        Ok(minified) => minified.to_string().trim().to_string(),
+        Err(_) => source.to_string(),
+    }
+}
+
+pub fn js(source: &str) -> String {
+    minifier::js::minify(source).to_string().trim().to_string()
+}
+
+/// tag_end returns the length of the tag at the start of html, up to its closing `>`, skipping
+/// over quoted attribute values.
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// is_javascript is false for scripts holding data, such as json-ld.
+fn is_javascript(tag: &str) -> bool {
+    let tag = tag.to_ascii_lowercase();
+    !tag.contains("type=") || tag.contains("javascript") || tag.contains("module")
+}
+
+fn push_collapsed(text: &str, out: &mut String) {
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !in_whitespace {
+                out.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            out.push(c);
+            in_whitespace = false;
+        }
+    }
+}
+
+/// html collapses the whitespace of rendered html and drops its comments. The content of `<pre>`
+/// and `<textarea>` elements and raw djot html is kept as it is, and inline styles and scripts
+/// are minified when css and js minification is on.
+pub fn html(config: &MinifyConfig, source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(raw) = rest.strip_prefix(RAW_START) {
+            let end = raw.find(RAW_END).unwrap_or(raw.len());
+            out.push_str(&raw[..end]);
+            rest = raw[end..].strip_prefix(RAW_END).unwrap_or("");
+        } else if rest.starts_with(RAW_END) {
+            rest = &rest[RAW_END.len()..];
+        } else if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + "-->".len());
+            // Conditional comments are markup for old browsers, not notes.
+            if rest.starts_with("<!--[if") {
+                out.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        } else if rest.starts_with('<') {
+            let end = tag_end(rest);
+            let tag = &rest[..end];
+            out.push_str(tag);
+            rest = &rest[end..];
+
+            let name = tag_name(tag);
+            if tag.starts_with("</")
+                || !matches!(name.as_str(), "pre" | "textarea" | "script" | "style")
+            {
+                continue;
+            }
+            let close = rest
+                .to_ascii_lowercase()
+                .find(&format!("</{}", name))
+                .unwrap_or(rest.len());
+            let body = &rest[..close];
+            match name.as_str() {
+                "script" if config.js && is_javascript(tag) => out.push_str(&js(body)),
+                "style" if config.css => out.push_str(&css(body)),
+                _ => out.push_str(body),
+            }
+            rest = &rest[close..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            push_collapsed(&rest[..end], &mut out);
+            rest = &rest[end..];
+        }
+    }
+    out.trim().to_string() + "\n"
+}
+
+/// minify_asset returns the minified contents of a copied css or js file, or None when the file
+/// is copied as it is.
+pub fn minify_asset(config: &Config, path: &Path) -> eyre::Result<Option<String>> {
+    let Some(minify) = active(config) else {
+        return Ok(None);
+    };
+    let name = path.to_string_lossy();
+    // Files which are already minified are left alone.
+    if name.ends_with(".min.css") || name.ends_with(".min.js") {
+        return Ok(None);
+    }
+    let extension = path.extension().and_then(|e| e.to_str());
+    match extension {
+        Some("css") if minify.css => Ok(Some(css(&fs::read_to_string(path)?))),
+        Some("js") if minify.js => Ok(Some(js(&fs::read_to_string(path)?))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DJOT: &str = "Some   *text*\n\n``` =html\n<div>\n  kept   as is\n</div>\n```\n";
+
+    fn render(mark_raw: bool) -> String {
+        let events = jotdown::Parser::new(DJOT);
+        if mark_raw {
+            jotdown::html::render_to_string(mark_raw_html(events))
+        } else {
+            jotdown::html::render_to_string(events)
+        }
+    }
+
+    #[test]
+    fn mark_raw_html_surrounds_raw_blocks() {
+        let marked = render(true);
+        assert!(marked.contains(&format!(
+            "{}<div>\n  kept   as is\n</div>{}",
+            RAW_START, RAW_END
+        )));
+        assert_eq!(strip_raw_markers(&marked), render(false));
+    }
+
+    #[test]
+    fn html_keeps_raw_blocks_and_drops_the_markers() {
+        let minified = html(&MinifyConfig::default(), &render(true));
+        assert!(minified.contains("<div>\n  kept   as is\n</div>"));
+        assert!(!minified.contains("quickstatic:raw"));
+    }
+}
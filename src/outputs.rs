@@ -0,0 +1,233 @@
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::{Config, DocumentData};
+
+/// OutputFormat is one entry of the `output_formats:` config section, an extra file rendered
+/// next to the html of a document, eg
+///
+/// ``` yaml
+/// output_formats:
+///   json:
+///     layout: default/page.json.liquid
+///   txt: {}
+/// outputs:
+///   docs/**/*.md: [json, txt]
+/// ```
+///
+/// Documents can also list their outputs in the `outputs` frontmatter.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct OutputFormat {
+    /// layout renders the output, with the same context as the html layout. Without a layout,
+    /// `json`, `md` and `txt` outputs have a built-in rendering.
+    #[serde(default)]
+    pub layout: Option<String>,
+    /// extension of the output file. It defaults to the name of the format.
+    #[serde(default)]
+    pub extension: Option<String>,
+}
+
+/// Output is an extra file to render for a document.
+pub struct Output {
+    pub name: String,
+    pub layout: Option<String>,
+    /// destination is the path of the file, next to the html of the document.
+    pub destination: String,
+}
+
+fn frontmatter_outputs(frontmatter: &Value) -> Option<Vec<String>> {
+    match frontmatter.get("outputs")? {
+        Value::Sequence(names) => Some(
+            names
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+        ),
+        Value::String(name) => Some(vec![name.clone()]),
+        _ => None,
+    }
+}
+
+/// document_outputs lists the extra outputs of an html document, from its `outputs` frontmatter,
+/// or else from the `outputs` config globs matching its source path.
+pub fn document_outputs(
+    config: &Config,
+    source_path: &str,
+    document: &DocumentData,
+) -> eyre::Result<Vec<Output>> {
+    let Some(stem) = document.file_destination_path.strip_suffix(".html") else {
+        return Ok(vec![]);
+    };
+    let names = frontmatter_outputs(&document.frontmatter).unwrap_or_else(|| {
+        let mut names: Vec<String> = vec![];
+        for (glob, globbed) in &config.outputs {
+            if !glob_match::glob_match(glob, source_path) {
+                continue;
+            }
+            for name in globbed {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        names
+    });
+
+    let mut outputs = vec![];
+    for name in names.into_iter().filter(|name| name != "html") {
+        let format = match config.output_formats.get(&name) {
+            Some(format) => format.clone(),
+            None if builtin(&name) => OutputFormat::default(),
+            None => {
+                return Err(eyre!(
+                    "unknown output format `{}` for {}, expecting one of the `output_formats` \
+                     in the config, or json, md or txt",
+                    name,
+                    document.file_path
+                ))
+            }
+        };
+        if format.layout.is_none() && !builtin(&name) {
+            return Err(eyre!(
+                "output format `{}` needs a layout in the `output_formats` config",
+                name
+            ));
+        }
+        let extension = format.extension.unwrap_or_else(|| name.clone());
+        outputs.push(Output {
+            destination: format!("{}.{}", stem, extension.trim_start_matches('.')),
+            layout: format.layout,
+            name,
+        });
+    }
+    Ok(outputs)
+}
+
+fn builtin(name: &str) -> bool {
+    matches!(name, "json" | "md" | "txt")
+}
+
+/// strip_tags turns rendered html into plain text.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    // Collapse the blank lines left behind by block tags.
+    let mut lines: Vec<&str> = vec![];
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string() + "\n"
+}
+
+/// render_builtin renders the outputs which have no layout: the document data as json, its
+/// markdown source with liquid evaluated, or its content as plain text.
+pub fn render_builtin(name: &str, document: &DocumentData) -> eyre::Result<String> {
+    match name {
+        "json" => {
+            let json = serde_json::json!({
+                "title": document.frontmatter.get("title"),
+                "permalink": document.permalink,
+                "lang": document.lang,
+                "frontmatter": document.frontmatter,
+                "toc": document.toc,
+                "content": document.content,
+            });
+            Ok(serde_json::to_string_pretty(&json)? + "\n")
+        }
+        // Liquid documents have no markdown, so they get their rendered content.
+        "md" if document.file_path.ends_with(".md") => Ok(document.markdown_processed.clone()),
+        "md" => Ok(document.content.clone()),
+        "txt" => Ok(strip_tags(&document.content)),
+        _ => Err(eyre!("output format `{}` needs a layout", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    const CONFIG: &str = "
+output_formats:
+  feed: {layout: default/page.feed.liquid, extension: .rss.xml}
+  ical: {}
+outputs:
+  'docs/**/*.md': [json, txt]
+  '**/*.md': [txt, md]
+";
+
+    fn destinations(source_path: &str, destination: &str, frontmatter: &str) -> Vec<String> {
+        let config = test_config(CONFIG);
+        let document = test_document(&config, source_path, destination, frontmatter);
+        document_outputs(&config, source_path, &document)
+            .unwrap()
+            .into_iter()
+            .map(|output| output.destination)
+            .collect()
+    }
+
+    #[test]
+    fn outputs_are_written_next_to_the_html() {
+        assert_eq!(
+            destinations("docs/intro.md", "/docs/intro/index.html", "{}"),
+            [
+                "/site/_quickstatic/public/docs/intro/index.json",
+                "/site/_quickstatic/public/docs/intro/index.txt",
+                "/site/_quickstatic/public/docs/intro/index.md",
+            ]
+        );
+        assert_eq!(
+            destinations("about.md", "/about.html", "outputs: [html, feed]"),
+            ["/site/_quickstatic/public/about.rss.xml"]
+        );
+        assert!(destinations("feed.liquid", "/feed.xml", "outputs: [json]").is_empty());
+    }
+
+    #[test]
+    fn unknown_formats_and_formats_without_layouts_fail() {
+        let config = test_config(CONFIG);
+        for (frontmatter, message) in [
+            (
+                "outputs: pdf",
+                "unknown output format `pdf` for /site/about.md",
+            ),
+            (
+                "outputs: ical",
+                "output format `ical` needs a layout in the `output_formats` config",
+            ),
+        ] {
+            let document = test_document(&config, "about.md", "/about/index.html", frontmatter);
+            let error = document_outputs(&config, "about.md", &document)
+                .err()
+                .unwrap();
+            assert!(error.to_string().starts_with(message), "{}", error);
+        }
+    }
+
+    #[test]
+    fn strip_tags_keeps_the_text() {
+        assert_eq!(
+            strip_tags("<h1>Fish &amp; chips</h1>\n\n\n<p>1 &lt; 2</p>\n"),
+            "Fish & chips\n\n1 < 2\n"
+        );
+    }
+}
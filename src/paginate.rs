@@ -0,0 +1,273 @@
+use eyre::{eyre, WrapErr};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::sort::{safe_property_getter, sort_by_spec};
+use crate::where_glob::glob_matches;
+use crate::DocumentData;
+
+fn default_per_page() -> usize {
+    10
+}
+
+/// PaginateConfig is the `paginate:` block in the frontmatter of a listing page, eg
+///
+/// ``` yaml
+/// paginate:
+///   collection: blog/**/*.md
+///   where:
+///     frontmatter.tags: rust
+///   sort: frontmatter.date desc
+///   per_page: 5
+/// ```
+#[derive(Deserialize, Debug)]
+struct PaginateConfig {
+    /// collection is a glob matched against source paths relative to the site root.
+    collection: String,
+    /// filter keeps the documents whose properties match the given globs, as `where_glob` does.
+    #[serde(default, rename = "where")]
+    filter: IndexMap<String, String>,
+    /// sort orders the documents, the same way as the sort filter.
+    #[serde(default)]
+    sort: Option<String>,
+    #[serde(default = "default_per_page")]
+    per_page: usize,
+}
+
+/// PageLink points at one page of a paginated listing.
+#[derive(Clone, Debug, Serialize)]
+pub struct PageLink {
+    pub number: usize,
+    pub permalink: String,
+}
+
+/// Paginator is exposed to templates as `paginator` on every page of a paginated listing.
+#[derive(Clone, Debug, Serialize)]
+pub struct Paginator {
+    /// items are the documents on the current page.
+    pub items: Vec<DocumentData>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total_pages: usize,
+    pub total_items: usize,
+    pub previous_page: Option<usize>,
+    pub previous_page_permalink: Option<String>,
+    pub next_page: Option<usize>,
+    pub next_page_permalink: Option<String>,
+    pub first_page_permalink: String,
+    pub last_page_permalink: String,
+    pub pages: Vec<PageLink>,
+}
+
+/// page_permalink returns the permalink of a page of a listing. The first page keeps the listing's
+/// own permalink, eg `/blog/`, and the others are nested under it, eg `/blog/page/2/`.
+fn page_permalink(permalink: &str, page: usize) -> String {
+    if page == 1 {
+        return permalink.to_string();
+    }
+    let dir = match permalink.strip_suffix(".html") {
+        Some(stem) => format!("{}/", stem),
+        None => permalink.to_string(),
+    };
+    format!("{}page/{}/", dir, page)
+}
+
+/// collection returns the documents a listing paginates over, filtered and sorted.
+fn collection(
+    root_dir: &str,
+    document: &DocumentData,
+    config: &PaginateConfig,
+    documents: &[DocumentData],
+) -> eyre::Result<Vec<DocumentData>> {
+    let mut items = vec![];
+    for candidate in documents {
+        if candidate.file_path == document.file_path
+            || !glob_match::glob_match(&config.collection, &candidate.source_path(root_dir))
+        {
+            continue;
+        }
+        let value = liquid::model::to_value(candidate)?;
+        let matches = config.filter.iter().all(|(property, pattern)| {
+            glob_matches(&safe_property_getter(&value, property), pattern)
+        });
+        if matches {
+            items.push((value, candidate.clone()));
+        }
+    }
+    match &config.sort {
+        Some(spec) => Ok(sort_by_spec(items, spec).wrap_err("invalid `paginate.sort`")?),
+        None => Ok(items.into_iter().map(|(_, item)| item).collect()),
+    }
+}
+
+/// paginate splits every document with a `paginate:` block in its frontmatter into one document
+/// per page of its collection, each with its paginator. Other documents are returned as they are.
+pub fn paginate(
+    root_dir: &str,
+    documents: Vec<DocumentData>,
+    all_documents: &[DocumentData],
+) -> eyre::Result<Vec<DocumentData>> {
+    let mut pages = Vec::with_capacity(documents.len());
+    for document in documents {
+        let Some(paginate) = document.frontmatter.get("paginate") else {
+            pages.push(document);
+            continue;
+        };
+        let config: PaginateConfig = serde_yaml::from_value(paginate.clone()).wrap_err(format!(
+            "invalid `paginate` frontmatter in {}",
+            document.file_path
+        ))?;
+        if config.per_page == 0 {
+            return Err(eyre!(
+                "invalid `paginate` frontmatter in {}: per_page must be greater than 0",
+                document.file_path
+            ));
+        }
+
+        let items = collection(root_dir, &document, &config, all_documents)
+            .wrap_err(format!("unable to paginate {}", document.file_path))?;
+        let total_items = items.len();
+        let total_pages = total_items.div_ceil(config.per_page).max(1);
+        let permalinks: Vec<String> = (1..=total_pages)
+            .map(|page| page_permalink(&document.permalink, page))
+            .collect();
+        let page_links: Vec<PageLink> = permalinks
+            .iter()
+            .enumerate()
+            .map(|(i, permalink)| PageLink {
+                number: i + 1,
+                permalink: permalink.clone(),
+            })
+            .collect();
+
+        for page in 1..=total_pages {
+            let mut page_document = document.clone();
+            if page > 1 {
+                page_document.permalink = permalinks[page - 1].clone();
+                page_document.file_destination_path = format!(
+                    "{}/{}index.html",
+                    crate::public_dir(root_dir),
+                    page_document.permalink.trim_start_matches('/')
+                );
+            }
+            let previous_page = (page > 1).then(|| page - 1);
+            let next_page = (page < total_pages).then(|| page + 1);
+            page_document.paginator = Some(Paginator {
+                items: items
+                    .iter()
+                    .skip((page - 1) * config.per_page)
+                    .take(config.per_page)
+                    .cloned()
+                    .collect(),
+                page,
+                per_page: config.per_page,
+                total_pages,
+                total_items,
+                previous_page,
+                previous_page_permalink: previous_page.map(|p| permalinks[p - 1].clone()),
+                next_page,
+                next_page_permalink: next_page.map(|p| permalinks[p - 1].clone()),
+                first_page_permalink: permalinks[0].clone(),
+                last_page_permalink: permalinks[total_pages - 1].clone(),
+                pages: page_links.clone(),
+            });
+            pages.push(page_document);
+        }
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    const LISTING: &str = "paginate: {collection: 'blog/*/index.md', sort: title, per_page: 2}";
+
+    fn posts(count: usize) -> Vec<DocumentData> {
+        let config = test_config("");
+        (1..=count)
+            .map(|i| {
+                test_document(
+                    &config,
+                    &format!("blog/post-{}/index.md", i),
+                    &format!("/blog/post-{}/index.html", i),
+                    &format!("title: Post {}", i),
+                )
+            })
+            .collect()
+    }
+
+    fn paginate_listing(documents: Vec<DocumentData>) -> Vec<DocumentData> {
+        let listing = test_document(
+            &test_config(""),
+            "blog/index.md",
+            "/blog/index.html",
+            LISTING,
+        );
+        let mut all = documents;
+        all.push(listing.clone());
+        paginate("/site", vec![listing], &all).unwrap()
+    }
+
+    #[test]
+    fn page_permalink_nests_pages_under_the_listing() {
+        assert_eq!(page_permalink("/blog/", 1), "/blog/");
+        assert_eq!(page_permalink("/blog/", 3), "/blog/page/3/");
+        assert_eq!(page_permalink("/archive.html", 2), "/archive/page/2/");
+    }
+
+    #[test]
+    fn paginate_creates_a_page_per_per_page_items() {
+        let pages = paginate_listing(posts(5));
+        let permalinks: Vec<&str> = pages.iter().map(|p| p.permalink.as_str()).collect();
+        assert_eq!(permalinks, ["/blog/", "/blog/page/2/", "/blog/page/3/"]);
+        assert_eq!(
+            pages[1].file_destination_path,
+            "/site/_quickstatic/public/blog/page/2/index.html"
+        );
+
+        let paginator = pages[2].paginator.as_ref().unwrap();
+        assert_eq!(paginator.total_pages, 3);
+        assert_eq!(paginator.total_items, 5);
+        assert_eq!(paginator.items.len(), 1);
+        assert_eq!(
+            paginator.previous_page_permalink.as_deref(),
+            Some("/blog/page/2/")
+        );
+        assert_eq!(paginator.next_page_permalink, None);
+        assert_eq!(paginator.last_page_permalink, "/blog/page/3/");
+    }
+
+    #[test]
+    fn an_empty_collection_still_has_a_first_page() {
+        let pages = paginate_listing(vec![]);
+        assert_eq!(pages.len(), 1);
+        let paginator = pages[0].paginator.as_ref().unwrap();
+        assert_eq!(paginator.total_pages, 1);
+        assert!(paginator.items.is_empty());
+        assert_eq!(paginator.next_page, None);
+    }
+
+    #[test]
+    fn a_full_last_page_adds_no_empty_page() {
+        let pages = paginate_listing(posts(4));
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].paginator.as_ref().unwrap().items.len(), 2);
+    }
+
+    #[test]
+    fn per_page_must_be_positive() {
+        let config = test_config("");
+        let listing = test_document(
+            &config,
+            "blog/index.md",
+            "/blog/index.html",
+            "paginate: {collection: 'blog/*/index.md', per_page: 0}",
+        );
+        let error = paginate("/site", vec![listing], &[]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("per_page must be greater than 0"));
+    }
+}
@@ -0,0 +1,246 @@
+use eyre::eyre;
+use serde_yaml::Value;
+
+use crate::sort::as_date_time;
+use crate::taxonomy::slugify;
+use crate::Config;
+
+/// stem is the name a document is known by: the file name without extensions, or the directory
+/// name for index files, eg `blog/hello/index.md` and `blog/hello.md` are both `hello`.
+fn stem(source_path: &str) -> &str {
+    let without_index = source_path
+        .strip_suffix("/index.md")
+        .or_else(|| source_path.strip_suffix("/index.html.liquid"))
+        .unwrap_or(source_path);
+    let name = without_index.rsplit('/').next().unwrap_or(without_index);
+    name.split('.').next().unwrap_or(name)
+}
+
+/// path is the source path without extensions and without a trailing index file.
+fn path(source_path: &str) -> String {
+    let (dir, name) = source_path.rsplit_once('/').unwrap_or(("", source_path));
+    let name = name.split('.').next().unwrap_or(name);
+    match (dir, name) {
+        (dir, "index") => dir.to_string(),
+        ("", name) => name.to_string(),
+        (dir, name) => format!("{}/{}", dir, name),
+    }
+}
+
+fn frontmatter_str<'a>(frontmatter: &'a Value, key: &str) -> Option<&'a str> {
+    frontmatter
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.trim().is_empty())
+}
+
+/// expand replaces the placeholders in a permalink pattern such as `/:year/:month/:slug/`. Pages
+/// generated from data records can also use the fields of their record, eg `/integrations/:id/`.
+fn expand(
+    pattern: &str,
+    source_path: &str,
+    frontmatter: &Value,
+    data: &Value,
+) -> eyre::Result<String> {
+    let date = frontmatter
+        .get("date")
+        .and_then(|date| liquid::model::to_value(date).ok())
+        .and_then(|date| as_date_time(&date));
+    let slug = match frontmatter_str(frontmatter, "slug") {
+        Some(slug) => slugify(slug),
+        None => slugify(stem(source_path)),
+    };
+
+    let mut expanded = String::with_capacity(pattern.len());
+    for (i, segment) in pattern.split('/').enumerate() {
+        if i > 0 {
+            expanded.push('/');
+        }
+        let Some(placeholder) = segment.strip_prefix(':') else {
+            expanded.push_str(segment);
+            continue;
+        };
+        let value = match placeholder {
+            "year" | "month" | "day" => {
+                let date = date.ok_or_else(|| {
+                    eyre!(
+                        "permalink `{}` for {} uses :{} but the document has no valid `date`",
+                        pattern,
+                        source_path,
+                        placeholder
+                    )
+                })?;
+                match placeholder {
+                    "year" => format!("{:04}", date.year()),
+                    "month" => format!("{:02}", date.month()),
+                    _ => format!("{:02}", date.day()),
+                }
+            }
+            "slug" => slug.clone(),
+            "title" => frontmatter_str(frontmatter, "title")
+                .map(slugify)
+                .unwrap_or_else(|| slug.clone()),
+            "path" => path(source_path),
+            "section" => source_path
+                .split_once('/')
+                .map(|(section, _)| section.to_string())
+                .unwrap_or_default(),
+            field => match data.get(field) {
+                Some(Value::String(s)) => slugify(s),
+                Some(Value::Number(n)) => n.to_string(),
+                _ => {
+                    return Err(eyre!(
+                        "permalink `{}` for {} uses unknown placeholder :{}, expected one of \
+                         :year, :month, :day, :slug, :title, :path, :section or a field of the \
+                         data record",
+                        pattern,
+                        source_path,
+                        placeholder
+                    ))
+                }
+            },
+        };
+        expanded.push_str(&value);
+    }
+
+    // Placeholders may be empty, eg :section for top level documents, so collapse the slashes.
+    let mut permalink = String::with_capacity(expanded.len() + 1);
+    for c in format!("/{}", expanded).chars() {
+        if !(c == '/' && permalink.ends_with('/')) {
+            permalink.push(c);
+        }
+    }
+    Ok(permalink)
+}
+
+/// destination decides where a document is written, relative to the public directory. The
+/// frontmatter `permalink` wins over the permalink patterns in the config, which win over the
+/// default destination derived from the source path. Permalinks ending in `/` or without an
+/// extension are written to an `index.html` in that directory, and with pretty_urls so are other
+/// html documents, eg `about.md` is written to `about/index.html`.
+pub fn destination(
+    config: &Config,
+    source_path: &str,
+    default_destination: &str,
+    frontmatter: &Value,
+    data: &Value,
+) -> eyre::Result<String> {
+    let pattern = frontmatter_str(frontmatter, "permalink").or_else(|| {
+        config
+            .permalinks
+            .iter()
+            .find(|(glob, _)| glob_match::glob_match(glob, source_path))
+            .map(|(_, pattern)| pattern.as_str())
+    });
+
+    let destination = match pattern {
+        Some(pattern) => {
+            let permalink = expand(pattern, source_path, frontmatter, data)?;
+            let has_extension = permalink
+                .rsplit('/')
+                .next()
+                .is_some_and(|name| name.contains('.'));
+            if permalink.ends_with('/') {
+                format!("{}index.html", permalink)
+            } else if !has_extension {
+                format!("{}/index.html", permalink)
+            } else {
+                permalink
+            }
+        }
+        None => format!("/{}", default_destination.trim_start_matches('/')),
+    };
+
+    // Hosts look for the 404 page at /404.html, so it keeps its name.
+    let keep_name = destination.ends_with("/index.html") || destination == "/404.html";
+    let destination = if config.pretty_urls && !keep_name {
+        match destination.strip_suffix(".html") {
+            Some(stem) => format!("{}/index.html", stem),
+            None => destination,
+        }
+    } else {
+        destination
+    };
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_config;
+
+    fn frontmatter(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn expand_fills_in_placeholders() {
+        let frontmatter = frontmatter("date: 2024-03-09\ntitle: Hello World");
+        let expand = |pattern| expand(pattern, "blog/hello/index.md", &frontmatter, &Value::Null);
+        assert_eq!(
+            expand("/:year/:month/:day/:slug/").unwrap(),
+            "/2024/03/09/hello/"
+        );
+        assert_eq!(expand("/:section/:title/").unwrap(), "/blog/hello-world/");
+        assert_eq!(expand("/:path/").unwrap(), "/blog/hello/");
+        assert!(expand("/:nope/").is_err());
+    }
+
+    #[test]
+    fn expand_collapses_empty_placeholders() {
+        let expanded = expand("/:section/:slug/", "about.md", &Value::Null, &Value::Null);
+        assert_eq!(expanded.unwrap(), "/about/");
+    }
+
+    #[test]
+    fn expand_needs_a_date_for_date_placeholders() {
+        assert!(expand("/:year/:slug/", "about.md", &Value::Null, &Value::Null).is_err());
+    }
+
+    #[test]
+    fn expand_reads_data_record_fields() {
+        let data = frontmatter("id: 42\nname: Big Query");
+        let expand = |pattern| expand(pattern, "integrations.md", &Value::Null, &data);
+        assert_eq!(expand("/integrations/:id/").unwrap(), "/integrations/42/");
+        assert_eq!(
+            expand("/integrations/:name/").unwrap(),
+            "/integrations/big-query/"
+        );
+    }
+
+    #[test]
+    fn destination_prefers_frontmatter_then_config_then_default() {
+        let config = test_config("permalinks:\n  \"blog/**/*.md\": /posts/:slug/");
+        let destination = |source, frontmatter: &str| {
+            destination(
+                &config,
+                source,
+                "default.html",
+                &self::frontmatter(frontmatter),
+                &Value::Null,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            destination("blog/a.md", "permalink: /special/"),
+            "/special/index.html"
+        );
+        assert_eq!(destination("blog/a.md", "{}"), "/posts/a/index.html");
+        assert_eq!(destination("about.md", "{}"), "/default.html");
+        assert_eq!(
+            destination("blog/a.md", "permalink: /feed.xml"),
+            "/feed.xml"
+        );
+        assert_eq!(destination("blog/a.md", "permalink: /a"), "/a/index.html");
+    }
+
+    #[test]
+    fn destination_with_pretty_urls_keeps_404() {
+        let config = test_config("pretty_urls: true");
+        let destination =
+            |default| destination(&config, "x.md", default, &Value::Null, &Value::Null).unwrap();
+        assert_eq!(destination("about.html"), "/about/index.html");
+        assert_eq!(destination("404.html"), "/404.html");
+        assert_eq!(destination("blog/index.html"), "/blog/index.html");
+    }
+}
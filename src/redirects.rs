@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use eyre::{eyre, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::feeds::xml_escape;
+use crate::urls::{absolute_url, relative_url};
+use crate::{public_dir, write_to_location, Config, DocumentData};
+
+/// RedirectFile is a host specific file listing every redirect, selected with the
+/// `redirect_files` config list.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectFile {
+    /// Netlify and Cloudflare Pages read `_redirects`.
+    Netlify,
+    /// Vercel reads the `redirects` of `vercel.json`.
+    Vercel,
+    /// Nginx can include `redirects.nginx.conf` in a server block.
+    Nginx,
+}
+
+/// Redirect sends an old url of the site to a new one. Both are root-relative site urls, unless
+/// `to` is an external url.
+#[derive(Debug, Clone)]
+struct Redirect {
+    from: String,
+    to: String,
+    /// source is what the redirect was declared in, for errors.
+    source: String,
+}
+
+fn normalize_url(url: &str) -> String {
+    format!("/{}", url.trim().trim_start_matches('/'))
+}
+
+/// stub_destination is the file, relative to the public directory, which serves a url.
+fn stub_destination(url: &str) -> String {
+    let path = url.trim_start_matches('/');
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if path.is_empty() || path.ends_with('/') {
+        format!("{}index.html", path)
+    } else if name.contains('.') {
+        path.to_string()
+    } else {
+        format!("{}/index.html", path)
+    }
+}
+
+fn document_aliases(document: &DocumentData) -> Vec<String> {
+    match document.frontmatter.get("aliases") {
+        Some(Value::Sequence(aliases)) => aliases
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(alias)) => vec![alias.clone()],
+        _ => vec![],
+    }
+}
+
+fn collect_redirects(config: &Config, documents: &[DocumentData]) -> Vec<Redirect> {
+    let mut redirects: Vec<Redirect> = config
+        .redirects
+        .iter()
+        .map(|(from, to)| Redirect {
+            from: normalize_url(from),
+            to: to.clone(),
+            source: "the `redirects` config".to_string(),
+        })
+        .collect();
+    for document in documents {
+        // Paginated listings share their frontmatter, so only their first page redirects.
+        if document.paginator.as_ref().is_some_and(|p| p.page > 1) {
+            continue;
+        }
+        for alias in document_aliases(document) {
+            redirects.push(Redirect {
+                from: normalize_url(&alias),
+                to: document.permalink.clone(),
+                source: format!("the `aliases` of {}", document.file_path),
+            });
+        }
+    }
+    redirects
+}
+
+/// check_collisions fails when a redirect would replace a generated page, or when two redirects
+/// are declared for the same url.
+fn check_collisions(
+    root_dir: &str,
+    redirects: &[Redirect],
+    documents: &[DocumentData],
+) -> eyre::Result<()> {
+    let public = public_dir(root_dir);
+    let pages: HashMap<&str, &DocumentData> = documents
+        .iter()
+        .map(|d| {
+            let destination = d.file_destination_path.strip_prefix(&public);
+            (destination.unwrap_or(&d.file_destination_path), d)
+        })
+        .map(|(destination, d)| (destination.trim_start_matches('/'), d))
+        .collect();
+
+    let mut seen: HashMap<String, &Redirect> = HashMap::new();
+    for redirect in redirects {
+        let destination = stub_destination(&redirect.from);
+        if let Some(page) = pages.get(destination.as_str()) {
+            return Err(eyre!(
+                "redirect from `{}` in {} collides with the page generated from {}",
+                redirect.from,
+                redirect.source,
+                page.file_path
+            ));
+        }
+        if let Some(other) = seen.insert(destination, redirect) {
+            return Err(eyre!(
+                "redirect from `{}` in {} is also declared in {}",
+                redirect.from,
+                redirect.source,
+                other.source
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// stub_html is the page left at an old url. Its body is ignored by pagefind, so the search index
+/// only holds the page it points at.
+fn stub_html(url: &str, canonical: &str) -> String {
+    let url = xml_escape(url);
+    let canonical = xml_escape(canonical);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Redirecting…</title>
+<link rel="canonical" href="{canonical}">
+<meta name="robots" content="noindex">
+<meta http-equiv="refresh" content="0; url={url}">
+</head>
+<body data-pagefind-ignore="all">
+<p>This page has moved to <a href="{url}">{url}</a>.</p>
+</body>
+</html>
+"#
+    )
+}
+
+fn netlify_redirects(redirects: &[(String, String)]) -> String {
+    redirects
+        .iter()
+        .map(|(from, to)| format!("{} {} 301\n", from, to))
+        .collect()
+}
+
+fn vercel_redirects(redirects: &[(String, String)]) -> eyre::Result<String> {
+    let redirects: Vec<serde_json::Value> = redirects
+        .iter()
+        .map(|(from, to)| {
+            serde_json::json!({
+                "source": from,
+                "destination": to,
+                "permanent": true,
+            })
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&serde_json::json!({ "redirects": redirects }))?;
+    Ok(json + "\n")
+}
+
+fn nginx_redirects(redirects: &[(String, String)]) -> String {
+    redirects
+        .iter()
+        .map(|(from, to)| format!("location = {} {{ return 301 {}; }}\n", from, to))
+        .collect()
+}
+
+/// write_redirects writes an html stub for every alias and configured redirect, which sends
+/// browsers on with a meta refresh and search engines with a canonical link, and the host
+/// specific redirect files selected in the config.
+pub fn write_redirects(
+    root_dir: &str,
+    config: &Config,
+    documents: &[DocumentData],
+) -> eyre::Result<()> {
+    let redirects = collect_redirects(config, documents);
+    check_collisions(root_dir, &redirects, documents)?;
+
+    let public = public_dir(root_dir);
+    for redirect in &redirects {
+        let html = stub_html(
+            &relative_url(&config.base_path, &redirect.to),
+            &absolute_url(&config.base_url, &redirect.to),
+        );
+        write_to_location(
+            format!("{}/{}", public, stub_destination(&redirect.from)),
+            html.as_bytes(),
+        )
+        .wrap_err(format!(
+            "unable to write the redirect from `{}`",
+            redirect.from
+        ))?;
+    }
+
+    // Hosts see the urls the site is deployed under, so they include the base_path.
+    let urls: Vec<(String, String)> = redirects
+        .iter()
+        .map(|r| {
+            (
+                relative_url(&config.base_path, &r.from),
+                relative_url(&config.base_path, &r.to),
+            )
+        })
+        .collect();
+    for file in &config.redirect_files {
+        let (name, contents) = match file {
+            RedirectFile::Netlify => ("_redirects", netlify_redirects(&urls)),
+            RedirectFile::Vercel => ("vercel.json", vercel_redirects(&urls)?),
+            RedirectFile::Nginx => ("redirects.nginx.conf", nginx_redirects(&urls)),
+        };
+        write_to_location(format!("{}/{}", public, name), contents.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls() -> Vec<(String, String)> {
+        vec![
+            ("/old/".to_string(), "/new/".to_string()),
+            (
+                "/feed".to_string(),
+                "https://other.org/feed.xml".to_string(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn stub_destination_serves_the_url() {
+        assert_eq!(stub_destination("/old/"), "old/index.html");
+        assert_eq!(stub_destination("/old"), "old/index.html");
+        assert_eq!(stub_destination("/old.html"), "old.html");
+        assert_eq!(stub_destination("/"), "index.html");
+    }
+
+    #[test]
+    fn stub_html_escapes_the_urls() {
+        let html = stub_html("/new/?a=1&b=\"<x>", "https://example.org/new/");
+        assert!(html.contains(
+            r#"<meta http-equiv="refresh" content="0; url=/new/?a=1&amp;b=&quot;&lt;x&gt;">"#
+        ));
+        assert!(html.contains(r#"<a href="/new/?a=1&amp;b=&quot;&lt;x&gt;">"#));
+        assert!(html.contains(r#"<link rel="canonical" href="https://example.org/new/">"#));
+        assert!(html.contains(r#"<body data-pagefind-ignore="all">"#));
+    }
+
+    #[test]
+    fn netlify_lists_a_redirect_per_line() {
+        assert_eq!(
+            netlify_redirects(&urls()),
+            "/old/ /new/ 301\n/feed https://other.org/feed.xml 301\n"
+        );
+    }
+
+    #[test]
+    fn vercel_lists_permanent_redirects() {
+        let json: serde_json::Value =
+            serde_json::from_str(&vercel_redirects(&urls()).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"redirects": [
+                {"source": "/old/", "destination": "/new/", "permanent": true},
+                {"source": "/feed", "destination": "https://other.org/feed.xml", "permanent": true},
+            ]})
+        );
+    }
+
+    #[test]
+    fn nginx_returns_301_for_exact_locations() {
+        assert_eq!(
+            nginx_redirects(&urls()),
+            "location = /old/ { return 301 /new/; }\nlocation = /feed { return 301 https://other.org/feed.xml; }\n"
+        );
+    }
+}
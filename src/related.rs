@@ -0,0 +1,274 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::hierarchy::PageRef;
+use crate::taxonomy::LanguageTaxonomies;
+use crate::DocumentData;
+
+/// RelatedConfig is the `related:` config section, which weighs how documents are related, eg
+///
+/// ``` yaml
+/// related:
+///   limit: 3
+///   taxonomy_weight: 2
+///   content_weight: 1
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RelatedConfig {
+    /// limit is the most related documents listed per document.
+    pub limit: usize,
+    /// taxonomy_weight is added for every taxonomy term two documents share.
+    pub taxonomy_weight: f64,
+    /// content_weight scales the similarity of the words in two documents, between 0 and 1.
+    pub content_weight: f64,
+}
+
+impl Default for RelatedConfig {
+    fn default() -> Self {
+        RelatedConfig {
+            limit: 5,
+            taxonomy_weight: 1.0,
+            content_weight: 1.0,
+        }
+    }
+}
+
+/// opted_out is true for documents with `related: false` in their frontmatter. They neither get
+/// related documents nor show up in those of others.
+fn opted_out(document: &DocumentData) -> bool {
+    document.frontmatter.get("related") == Some(&Value::Bool(false))
+}
+
+fn words(text: &str) -> HashMap<String, f64> {
+    let mut counts = HashMap::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+    {
+        *counts.entry(word.to_lowercase()).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+/// tf_idf weighs the words of every document by how rare they are across documents, so common
+/// words do not make documents look alike.
+fn tf_idf(bodies: &[HashMap<String, f64>]) -> Vec<HashMap<String, f64>> {
+    let mut document_frequency: HashMap<&str, f64> = HashMap::new();
+    for body in bodies {
+        for word in body.keys() {
+            *document_frequency.entry(word).or_insert(0.0) += 1.0;
+        }
+    }
+    let total = bodies.len() as f64;
+    bodies
+        .iter()
+        .map(|body| {
+            body.iter()
+                .map(|(word, count)| {
+                    let idf = (total / document_frequency[word.as_str()]).ln();
+                    (word.clone(), count * idf)
+                })
+                .filter(|(_, weight)| *weight > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+fn cosine_similarity(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let dot: f64 = a
+        .iter()
+        .filter_map(|(word, weight)| b.get(word).map(|other| weight * other))
+        .sum();
+    let norm = |v: &HashMap<String, f64>| v.values().map(|w| w * w).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// related_documents ranks, for every markdown document, the other documents in its language by
+/// the taxonomy terms they share and by the similarity of their content. The result is keyed by
+/// source file path.
+pub fn related_documents(
+    config: &RelatedConfig,
+    taxonomies: &LanguageTaxonomies,
+    documents: &[DocumentData],
+) -> HashMap<String, Vec<PageRef>> {
+    let candidates: Vec<&DocumentData> = documents
+        .iter()
+        .filter(|d| d.file_path.ends_with(".md") && !opted_out(d))
+        .collect();
+
+    let mut terms: HashMap<&str, HashSet<(&str, &str)>> = HashMap::new();
+    for (taxonomy, taxonomy_terms) in taxonomies.values().flatten() {
+        for (slug, term) in taxonomy_terms {
+            for page in &term.pages {
+                terms
+                    .entry(page.file_path.as_str())
+                    .or_default()
+                    .insert((taxonomy.as_str(), slug.as_str()));
+            }
+        }
+    }
+    let no_terms = HashSet::new();
+    let terms_of = |d: &DocumentData| terms.get(d.file_path.as_str()).unwrap_or(&no_terms);
+
+    let bodies: Vec<HashMap<String, f64>> =
+        candidates.iter().map(|d| words(&d.markdown_body)).collect();
+    let vectors = tf_idf(&bodies);
+
+    let mut related = HashMap::new();
+    for (i, document) in candidates.iter().enumerate() {
+        let mut scores: Vec<(f64, &DocumentData)> = candidates
+            .iter()
+            .enumerate()
+            .filter(|(j, other)| {
+                *j != i
+                    && other.lang == document.lang
+                    && other.translation_key != document.translation_key
+            })
+            .map(|(j, other)| {
+                let shared_terms = terms_of(document).intersection(terms_of(other)).count();
+                let score = config.taxonomy_weight * shared_terms as f64
+                    + config.content_weight * cosine_similarity(&vectors[i], &vectors[j]);
+                (score, *other)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        scores.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        related.insert(
+            document.file_path.clone(),
+            scores
+                .into_iter()
+                .take(config.limit)
+                .map(|(_, other)| PageRef::from(other))
+                .collect(),
+        );
+    }
+    related
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taxonomy::collect_taxonomies;
+    use crate::{test_config, test_document};
+
+    fn document(
+        config: &crate::Config,
+        source_path: &str,
+        frontmatter: &str,
+        body: &str,
+    ) -> DocumentData {
+        let language = crate::i18n::document_language(config, source_path);
+        let destination = format!(
+            "{}/{}/index.html",
+            crate::i18n::language_prefix(config, &language.lang),
+            language.translation_key.trim_end_matches(".md")
+        );
+        let mut document = test_document(config, source_path, &destination, frontmatter);
+        document.markdown_body = body.to_string();
+        document
+    }
+
+    fn related_permalinks(limit: usize) -> HashMap<String, Vec<String>> {
+        let config = test_config("languages: {fr: {}}\ntaxonomies: {tags: {}}");
+        let documents = vec![
+            document(
+                &config,
+                "borrow.md",
+                "tags: [rust]",
+                "The borrow checker enforces ownership rules.",
+            ),
+            document(
+                &config,
+                "garden.md",
+                "tags: [rust]",
+                "Tomatoes grow well in warm soil.",
+            ),
+            document(
+                &config,
+                "lifetimes.md",
+                "{}",
+                "The borrow checker enforces ownership and lifetimes.",
+            ),
+            document(
+                &config,
+                "private.md",
+                "{tags: [rust], related: false}",
+                "The borrow checker enforces ownership rules.",
+            ),
+            document(
+                &config,
+                "unrelated.md",
+                "{}",
+                "Sourdough needs flour, water, patience.",
+            ),
+            document(
+                &config,
+                "borrow.fr.md",
+                "tags: [rust]",
+                "Le borrow checker vérifie la propriété.",
+            ),
+            document(
+                &config,
+                "jardin.fr.md",
+                "tags: [rust]",
+                "Les tomates aiment la chaleur.",
+            ),
+        ];
+        let taxonomies = collect_taxonomies(&config, &documents);
+        let config = RelatedConfig {
+            limit,
+            ..RelatedConfig::default()
+        };
+        related_documents(&config, &taxonomies, &documents)
+            .into_iter()
+            .map(|(file_path, pages)| {
+                let permalinks = pages.into_iter().map(|page| page.permalink).collect();
+                (
+                    file_path.trim_start_matches("/site/").to_string(),
+                    permalinks,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shared_terms_outrank_similar_content() {
+        let related = related_permalinks(5);
+        assert_eq!(related["borrow.md"], ["/garden/", "/lifetimes/"]);
+        assert_eq!(related["lifetimes.md"], ["/borrow/"]);
+        assert!(related["unrelated.md"].is_empty());
+    }
+
+    #[test]
+    fn limit_keeps_the_best_documents() {
+        assert_eq!(related_permalinks(1)["borrow.md"], ["/garden/"]);
+    }
+
+    #[test]
+    fn opted_out_documents_are_left_out() {
+        let related = related_permalinks(5);
+        assert!(!related.contains_key("private.md"));
+        assert!(related
+            .values()
+            .flatten()
+            .all(|permalink| permalink != "/private/"));
+    }
+
+    #[test]
+    fn only_other_documents_of_the_same_language_are_related() {
+        let related = related_permalinks(5);
+        assert_eq!(related["borrow.fr.md"], ["/fr/jardin/"]);
+        assert_eq!(related["jardin.fr.md"], ["/fr/borrow/"]);
+        assert!(related["borrow.md"]
+            .iter()
+            .all(|permalink| !permalink.starts_with("/fr/")));
+    }
+}
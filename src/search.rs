@@ -0,0 +1,282 @@
+use std::path::Path;
+use std::sync::mpsc;
+
+use eyre::eyre;
+use indexmap::IndexMap;
+use lol_html::html_content::ContentType;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::feeds::xml_escape;
+use crate::{public_dir, Config};
+
+/// SearchConfig is the `search:` config section, which configures the pagefind search index
+/// generated after every build, eg
+///
+/// ``` yaml
+/// search:
+///   root_selector: main
+///   exclude_selectors: [nav, .toc]
+///   glob: "docs/**/*.html"
+///   meta:
+///     title: title
+///   filters:
+///     category: category
+///     version: versions
+///   sort:
+///     date: date
+/// ```
+///
+/// `meta`, `filters` and `sort` map pagefind names to frontmatter keys. Pages get their values
+/// as hidden elements at the end of the root element, so search results can show the frontmatter
+/// title, and be filtered by category or sorted by date.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub enabled: bool,
+    /// language indexes the whole site as one language, eg `en`. It defaults to the
+    /// default_language, or, on multilingual sites, to the lang attribute of each page.
+    pub language: Option<String>,
+    /// root_selector is the element of each page which is indexed.
+    pub root_selector: String,
+    /// exclude_selectors are elements within the root which are not indexed, eg navigation.
+    pub exclude_selectors: Vec<String>,
+    /// glob selects the html files to index, relative to the output directory.
+    pub glob: String,
+    /// output_subdir is the directory of the output the index is written to.
+    pub output_subdir: String,
+    /// meta are shown with search results, eg a `title` replacing the first heading of a page.
+    pub meta: IndexMap<String, String>,
+    /// filters narrow the results down. A list in the frontmatter gives the page every value.
+    pub filters: IndexMap<String, String>,
+    /// sort orders the results, instead of by relevance.
+    pub sort: IndexMap<String, String>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            enabled: true,
+            language: None,
+            root_selector: "html".to_string(),
+            exclude_selectors: vec![],
+            glob: "**/*.{html}".to_string(),
+            output_subdir: "pagefind".to_string(),
+            meta: IndexMap::new(),
+            filters: IndexMap::new(),
+            sort: IndexMap::new(),
+        }
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// attributes renders the hidden elements pagefind reads the metadata, filters and sort keys of a
+/// page from. Values are read from an attribute, so they may contain commas and colons.
+fn attributes(search: &SearchConfig, frontmatter: &Value) -> String {
+    let mut html = String::new();
+    let mut push = |kind: &str, name: &str, value: &str| {
+        html.push_str(&format!(
+            "<span hidden data-pagefind-{}=\"{}[data-value]\" data-value=\"{}\"></span>",
+            kind,
+            xml_escape(name),
+            xml_escape(value)
+        ));
+    };
+    for (name, key) in &search.meta {
+        if let Some(value) = frontmatter.get(key).and_then(scalar) {
+            push("meta", name, &value);
+        }
+    }
+    for (name, key) in &search.filters {
+        match frontmatter.get(key) {
+            Some(Value::Sequence(values)) => {
+                for value in values.iter().filter_map(scalar) {
+                    push("filter", name, &value);
+                }
+            }
+            Some(value) => {
+                if let Some(value) = scalar(value) {
+                    push("filter", name, &value);
+                }
+            }
+            None => {}
+        }
+    }
+    for (name, key) in &search.sort {
+        if let Some(value) = frontmatter.get(key).and_then(scalar) {
+            push("sort", name, &value);
+        }
+    }
+    html
+}
+
+/// annotate adds the search metadata, filters and sort keys from the frontmatter of a page to the
+/// end of its root element, where pagefind picks them up while indexing. With the default `html`
+/// root they go at the end of the body, as nothing but the head may come before it.
+pub fn annotate(search: &SearchConfig, frontmatter: &Value, html: &str) -> eyre::Result<String> {
+    if !search.enabled {
+        return Ok(html.to_string());
+    }
+    let attributes = attributes(search, frontmatter);
+    if attributes.is_empty() {
+        return Ok(html.to_string());
+    }
+    let selector = match search.root_selector.as_str() {
+        "html" => "body",
+        root_selector => root_selector,
+    };
+    let mut annotated = false;
+    let html = lol_html::rewrite_str(
+        html,
+        lol_html::RewriteStrSettings {
+            element_content_handlers: vec![lol_html::element!(selector, |el| {
+                if !annotated {
+                    el.append(&attributes, ContentType::Html);
+                    annotated = true;
+                }
+                Ok(())
+            })],
+            ..lol_html::RewriteStrSettings::default()
+        },
+    )
+    .map_err(|e| eyre!("could not add search metadata: {e}"))?;
+    Ok(html)
+}
+
+/// build_index indexes the generated site with pagefind, and writes the index and its search ui
+/// into the output directory.
+pub async fn build_index(root_dir: &str, config: &Config) -> eyre::Result<()> {
+    let search = &config.search;
+    if !search.enabled {
+        return Ok(());
+    }
+
+    // Multilingual sites get an index per language, which pagefind detects from the lang
+    // attribute of each page's html element. Other sites are indexed in their one language.
+    let language = search.language.clone().or_else(|| {
+        config
+            .languages
+            .is_empty()
+            .then(|| config.default_language.clone())
+    });
+    let options = pagefind::options::PagefindServiceConfig::builder()
+        .keep_index_url(true)
+        .root_selector(search.root_selector.clone())
+        .exclude_selectors(search.exclude_selectors.clone());
+    let options = match language {
+        Some(language) => options.force_language(language).build(),
+        None => options.build(),
+    };
+    let mut index = pagefind::api::PagefindIndex::new(Some(options))
+        .map_err(|e| eyre!("invalid search config: {e:?}"))?;
+
+    let public = public_dir(root_dir);
+    index
+        .add_directory(public.clone(), Some(search.glob.clone()))
+        .await
+        .map_err(|e| eyre!("pagefind add_directory failed: {e:?}"))?;
+    let output = Path::new(&public).join(search.output_subdir.trim_matches('/'));
+    index
+        .write_files(Some(output.to_string_lossy().to_string()))
+        .await
+        .map_err(|e| eyre!("pagefind write_files failed: {e:?}"))?;
+    Ok(())
+}
+
+/// BackgroundIndexer keeps the search index of the dev server fresh. Indexing runs on its own
+/// thread, so pages are served as soon as they are rebuilt, and the index follows shortly after.
+pub struct BackgroundIndexer {
+    requests: mpsc::Sender<()>,
+}
+
+impl BackgroundIndexer {
+    pub fn new(root_dir: &str) -> Self {
+        let (requests, received) = mpsc::channel::<()>();
+        let root_dir = root_dir.to_string();
+        // pagefind's indexing can not move between threads, so it gets a runtime of its own.
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => return println!("Search Index Error: {:?}\n", e),
+            };
+            while received.recv().is_ok() {
+                // Builds which finished while the index was being built are covered by one run.
+                while received.try_recv().is_ok() {}
+                let result = crate::load_config(&root_dir).and_then(|config| {
+                    runtime.block_on(build_index(&root_dir, &config))?;
+                    Ok(config.search.enabled)
+                });
+                match result {
+                    Err(e) => println!("Search Index Error: {:?}\n", e),
+                    Ok(true) => println!("Rebuilt search index \n"),
+                    // Sites with search disabled have no index to report on.
+                    Ok(false) => {}
+                }
+            }
+        });
+        BackgroundIndexer { requests }
+    }
+
+    /// refresh indexes the site as it was just built.
+    pub fn refresh(&self) {
+        let _ = self.requests.send(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str =
+        "<html><head><title>Hello</title></head><body><main>Hello</main></body></html>";
+    const SPAN: &str =
+        r#"<span hidden data-pagefind-filter="category[data-value]" data-value="news"></span>"#;
+
+    fn search(root_selector: &str) -> SearchConfig {
+        let mut search = SearchConfig {
+            root_selector: root_selector.to_string(),
+            ..SearchConfig::default()
+        };
+        search
+            .filters
+            .insert("category".to_string(), "category".to_string());
+        search
+    }
+
+    #[test]
+    fn annotate_appends_to_the_body_of_the_html_root() {
+        let frontmatter = serde_yaml::from_str("category: news").unwrap();
+        let html = annotate(&search("html"), &frontmatter, PAGE).unwrap();
+        assert_eq!(
+            html,
+            format!(
+                "<html><head><title>Hello</title></head><body><main>Hello</main>{}</body></html>",
+                SPAN
+            )
+        );
+    }
+
+    #[test]
+    fn annotate_appends_to_other_roots() {
+        let frontmatter = serde_yaml::from_str("category: news").unwrap();
+        let html = annotate(&search("main"), &frontmatter, PAGE).unwrap();
+        assert_eq!(
+            html,
+            format!(
+                "<html><head><title>Hello</title></head><body><main>Hello{}</main></body></html>",
+                SPAN
+            )
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use std::io::Write;
+
+use liquid_core::error::ResultLiquidReplaceExt;
+use liquid_core::model::ScalarCow;
+use liquid_core::Language;
+use liquid_core::Renderable;
+use liquid_core::Runtime;
+use liquid_core::ValueView;
+use liquid_core::{Error, Result};
+use liquid_core::{ParseTag, TagReflection, TagTokenIter};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::feeds::{rfc3339, xml_escape};
+use crate::sort::as_date_time;
+use crate::urls::absolute_url;
+
+/// SeoConfig is the `seo:` config section, with the site wide values the seo tag falls back to
+/// when a page's frontmatter has none, eg
+///
+/// ``` yaml
+/// seo:
+///   description: Guides and reference for Example.
+///   author: Example Team
+///   image: /images/social.png
+///   twitter: "@example"
+///   locale: en_GB
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SeoConfig {
+    pub description: Option<String>,
+    pub author: Option<String>,
+    /// image is shown when pages without an `image` are shared.
+    pub image: Option<String>,
+    /// twitter is the site's handle, for the `twitter:site` tag.
+    pub twitter: Option<String>,
+    /// locale is the `og:locale` of the pages, eg `en_GB`. It defaults to the page language with
+    /// its most common territory, eg `en_US` for `en`.
+    pub locale: Option<String>,
+}
+
+/// SeoTag outputs the `<title>`, description, canonical link, Open Graph and Twitter tags of the
+/// page being rendered, and its schema.org JSON-LD, eg `{% seo %}` in the `<head>` of a layout.
+/// Values come from the `title`, `description`, `image`, `date`, `lastmod` and `author`
+/// frontmatter, and fall back to the site config.
+#[derive(Clone, Debug)]
+pub struct SeoTag {
+    pub site_title: String,
+    pub base_url: String,
+    pub lang: String,
+    pub seo: SeoConfig,
+}
+
+impl TagReflection for SeoTag {
+    fn tag(&self) -> &'static str {
+        "seo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Outputs the title, meta, Open Graph and Twitter tags and JSON-LD of the page."
+    }
+}
+
+impl ParseTag for SeoTag {
+    fn parse(
+        &self,
+        mut arguments: TagTokenIter<'_>,
+        _options: &Language,
+    ) -> Result<Box<dyn Renderable>> {
+        arguments.expect_nothing()?;
+        Ok(Box::new(Seo { tag: self.clone() }))
+    }
+
+    fn reflection(&self) -> &dyn TagReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Seo {
+    tag: SeoTag,
+}
+
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// date reads a frontmatter date, eg `2024-03-01`, as an RFC 3339 timestamp.
+fn date(value: &Value) -> Option<String> {
+    let value = liquid_core::Value::scalar(string(value)?);
+    as_date_time(&value).map(|date| rfc3339(&date))
+}
+
+/// author reads the `author` frontmatter, either a name or a mapping with a `name`.
+fn author(value: &Value) -> Option<String> {
+    string(value).or_else(|| string(value.get("name")?))
+}
+
+/// og_locale turns a language tag into the `language_TERRITORY` form of `og:locale`, eg `pt-br`
+/// into `pt_BR`. Bare languages get their most common territory, eg `en_US` for `en`.
+fn og_locale(lang: &str) -> String {
+    let mut parts = lang.split(['-', '_']);
+    let language = parts.next().unwrap_or_default().to_lowercase();
+    if let Some(territory) = parts.next().filter(|territory| !territory.is_empty()) {
+        return format!("{}_{}", language, territory.to_uppercase());
+    }
+    let territory = match language.as_str() {
+        "ar" => "AR",
+        "cs" => "CZ",
+        "da" => "DK",
+        "el" => "GR",
+        "en" => "US",
+        "he" => "IL",
+        "hi" => "IN",
+        "ja" => "JP",
+        "ko" => "KR",
+        "nb" | "nn" => "NO",
+        "pt" => "BR",
+        "sv" => "SE",
+        "uk" => "UA",
+        "vi" => "VN",
+        "zh" => "CN",
+        // Most languages share their code with their main territory, eg fr_FR and de_DE.
+        _ => return format!("{}_{}", language, language.to_uppercase()),
+    };
+    format!("{}_{}", language, territory)
+}
+
+/// json_ld renders JSON-LD for a `<script>` element, escaping `<` so values can not close it.
+fn json_ld(value: &Value) -> String {
+    value.to_string().replace('<', "\\u003c")
+}
+
+impl Seo {
+    fn render(&self, this: &Value) -> String {
+        let tag = &self.tag;
+        let frontmatter = &this["frontmatter"];
+        let field = |key: &str| string(&frontmatter[key]);
+
+        let url = field("canonical_url").unwrap_or_else(|| {
+            let permalink = this["permalink"].as_str().unwrap_or("/");
+            absolute_url(&tag.base_url, permalink)
+        });
+        let site_url = absolute_url(&tag.base_url, "/");
+        let page_title = field("title");
+        let title = match &page_title {
+            Some(title) if *title != tag.site_title => format!("{} | {}", title, tag.site_title),
+            _ => tag.site_title.clone(),
+        };
+        let description = field("description").or_else(|| tag.seo.description.clone());
+        let image = field("image")
+            .or_else(|| tag.seo.image.clone())
+            .map(|image| absolute_url(&tag.base_url, &image));
+        let author = author(&frontmatter["author"]).or_else(|| tag.seo.author.clone());
+        let published = date(&frontmatter["date"]);
+        let modified = date(&frontmatter["lastmod"])
+            .or_else(|| date(&frontmatter["updated"]))
+            .or_else(|| published.clone());
+        let is_article = published.is_some();
+
+        let mut tags = vec![format!("<title>{}</title>", xml_escape(&title))];
+        let mut meta = |attribute: &str, name: &str, content: &str| {
+            tags.push(format!(
+                "<meta {}=\"{}\" content=\"{}\">",
+                attribute,
+                name,
+                xml_escape(content)
+            ))
+        };
+        if let Some(description) = &description {
+            meta("name", "description", description);
+        }
+        if let Some(author) = &author {
+            meta("name", "author", author);
+        }
+        if frontmatter["noindex"] == Value::Bool(true) {
+            meta("name", "robots", "noindex");
+        }
+        meta(
+            "property",
+            "og:title",
+            page_title.as_ref().unwrap_or(&title),
+        );
+        meta("property", "og:site_name", &tag.site_title);
+        meta("property", "og:url", &url);
+        meta(
+            "property",
+            "og:type",
+            if is_article { "article" } else { "website" },
+        );
+        let locale = tag
+            .seo
+            .locale
+            .clone()
+            .unwrap_or_else(|| og_locale(&tag.lang));
+        meta("property", "og:locale", &locale);
+        if let Some(description) = &description {
+            meta("property", "og:description", description);
+        }
+        if let Some(image) = &image {
+            meta("property", "og:image", image);
+        }
+        if let Some(published) = &published {
+            meta("property", "article:published_time", published);
+        }
+        if let Some(modified) = &modified {
+            meta("property", "article:modified_time", modified);
+        }
+        let card = if image.is_some() {
+            "summary_large_image"
+        } else {
+            "summary"
+        };
+        meta("name", "twitter:card", card);
+        if let Some(twitter) = &tag.seo.twitter {
+            meta("name", "twitter:site", twitter);
+        }
+        meta(
+            "name",
+            "twitter:title",
+            page_title.as_ref().unwrap_or(&title),
+        );
+        if let Some(description) = &description {
+            meta("name", "twitter:description", description);
+        }
+        if let Some(image) = &image {
+            meta("name", "twitter:image", image);
+        }
+        tags.push(format!(
+            "<link rel=\"canonical\" href=\"{}\">",
+            xml_escape(&url)
+        ));
+
+        let mut graph = vec![json!({
+            "@type": "WebSite",
+            "@id": format!("{}#website", site_url),
+            "name": tag.site_title,
+            "url": site_url,
+            "inLanguage": tag.lang,
+            "description": tag.seo.description,
+        })];
+        if is_article {
+            graph.push(json!({
+                "@type": "Article",
+                "@id": format!("{}#article", url),
+                "headline": page_title.as_ref().unwrap_or(&title),
+                "description": description,
+                "image": image,
+                "datePublished": published,
+                "dateModified": modified,
+                "author": author.as_ref().map(|name| json!({"@type": "Person", "name": name})),
+                "inLanguage": tag.lang,
+                "mainEntityOfPage": url,
+                "isPartOf": {"@id": format!("{}#website", site_url)},
+            }));
+        }
+        let breadcrumbs = this["breadcrumbs"].as_array().cloned().unwrap_or_default();
+        if breadcrumbs.len() > 1 {
+            let items: Vec<Value> = breadcrumbs
+                .iter()
+                .enumerate()
+                .map(|(i, page)| {
+                    json!({
+                        "@type": "ListItem",
+                        "position": i + 1,
+                        "name": page["title"],
+                        "item": absolute_url(
+                            &tag.base_url,
+                            page["permalink"].as_str().unwrap_or("/"),
+                        ),
+                    })
+                })
+                .collect();
+            graph.push(json!({"@type": "BreadcrumbList", "itemListElement": items}));
+        }
+        // Values which are not known are left out, rather than written as null.
+        for node in &mut graph {
+            if let Value::Object(fields) = node {
+                fields.retain(|_, value| !value.is_null());
+            }
+        }
+        tags.push(format!(
+            "<script type=\"application/ld+json\">{}</script>",
+            json_ld(&json!({"@context": "https://schema.org", "@graph": graph}))
+        ));
+        tags.join("\n")
+    }
+}
+
+impl Renderable for Seo {
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        let this = runtime
+            .try_get(&[ScalarCow::new("this")])
+            .ok_or_else(|| Error::with_msg("the seo tag needs a page to render"))?;
+        let this = serde_json::to_value(this.to_value()).map_err(|e| {
+            Error::with_msg("Failed to read the page").context("cause", e.to_string())
+        })?;
+        write!(writer, "{}", self.render(&this)).replace("Failed to render")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seo_tag(lang: &str, seo: SeoConfig) -> Seo {
+        Seo {
+            tag: SeoTag {
+                site_title: "Example".to_string(),
+                base_url: "https://example.org/docs/".to_string(),
+                lang: lang.to_string(),
+                seo,
+            },
+        }
+    }
+
+    fn lines(html: &str) -> Vec<&str> {
+        html.lines().collect()
+    }
+
+    #[test]
+    fn og_locale_adds_a_territory() {
+        assert_eq!(og_locale("en"), "en_US");
+        assert_eq!(og_locale("fr"), "fr_FR");
+        assert_eq!(og_locale("pt-br"), "pt_BR");
+        assert_eq!(og_locale("zh_Hant"), "zh_HANT");
+    }
+
+    #[test]
+    fn pages_fall_back_to_the_site_config() {
+        let seo = SeoConfig {
+            description: Some("Guides & reference".to_string()),
+            image: Some("/social.png".to_string()),
+            twitter: Some("@example".to_string()),
+            ..SeoConfig::default()
+        };
+        let html = seo_tag("en", seo).render(&json!({
+            "permalink": "/about/",
+            "frontmatter": {"title": "Say \"hi\" <now>"},
+        }));
+        let (tags, script) = html.rsplit_once('\n').unwrap();
+        assert_eq!(
+            lines(tags),
+            [
+                "<title>Say &quot;hi&quot; &lt;now&gt; | Example</title>",
+                "<meta name=\"description\" content=\"Guides &amp; reference\">",
+                "<meta property=\"og:title\" content=\"Say &quot;hi&quot; &lt;now&gt;\">",
+                "<meta property=\"og:site_name\" content=\"Example\">",
+                "<meta property=\"og:url\" content=\"https://example.org/docs/about/\">",
+                "<meta property=\"og:type\" content=\"website\">",
+                "<meta property=\"og:locale\" content=\"en_US\">",
+                "<meta property=\"og:description\" content=\"Guides &amp; reference\">",
+                "<meta property=\"og:image\" content=\"https://example.org/docs/social.png\">",
+                "<meta name=\"twitter:card\" content=\"summary_large_image\">",
+                "<meta name=\"twitter:site\" content=\"@example\">",
+                "<meta name=\"twitter:title\" content=\"Say &quot;hi&quot; &lt;now&gt;\">",
+                "<meta name=\"twitter:description\" content=\"Guides &amp; reference\">",
+                "<meta name=\"twitter:image\" content=\"https://example.org/docs/social.png\">",
+                "<link rel=\"canonical\" href=\"https://example.org/docs/about/\">",
+            ]
+        );
+        assert_eq!(
+            script,
+            "<script type=\"application/ld+json\">{\"@context\":\"https://schema.org\",\"@graph\":[{\"@type\":\"WebSite\",\"@id\":\"https://example.org/docs/#website\",\"name\":\"Example\",\"url\":\"https://example.org/docs/\",\"inLanguage\":\"en\",\"description\":\"Guides & reference\"}]}</script>"
+        );
+    }
+
+    #[test]
+    fn dated_pages_are_articles() {
+        let seo = SeoConfig {
+            locale: Some("fr_CA".to_string()),
+            ..SeoConfig::default()
+        };
+        let html = seo_tag("fr", seo).render(&json!({
+            "permalink": "/blog/post/",
+            "frontmatter": {
+                "title": "</script> \"quoted\"",
+                "date": "2024-03-01",
+                "author": {"name": "Jane"},
+                "noindex": true,
+            },
+            "breadcrumbs": [
+                {"title": "Home", "permalink": "/"},
+                {"title": "Post", "permalink": "/blog/post/"},
+            ],
+        }));
+        assert!(html.contains("<meta name=\"author\" content=\"Jane\">"));
+        assert!(html.contains("<meta name=\"robots\" content=\"noindex\">"));
+        assert!(html.contains("<meta property=\"og:type\" content=\"article\">"));
+        assert!(html.contains("<meta property=\"og:locale\" content=\"fr_CA\">"));
+        assert!(html.contains(
+            "<meta property=\"article:published_time\" content=\"2024-03-01T00:00:00+00:00\">"
+        ));
+        assert!(html.contains("<meta name=\"twitter:card\" content=\"summary\">"));
+
+        let script = html.rsplit_once('\n').unwrap().1;
+        let json = script
+            .strip_prefix("<script type=\"application/ld+json\">")
+            .and_then(|json| json.strip_suffix("</script>"))
+            .unwrap();
+        assert!(!json.contains("</script>"));
+        let graph: Value = serde_json::from_str(json).unwrap();
+        let article = &graph["@graph"][1];
+        assert_eq!(article["@type"], "Article");
+        assert_eq!(article["headline"], "</script> \"quoted\"");
+        assert_eq!(article["author"]["name"], "Jane");
+        assert_eq!(article["dateModified"], "2024-03-01T00:00:00+00:00");
+        assert_eq!(
+            graph["@graph"][2]["itemListElement"][1]["item"],
+            "https://example.org/docs/blog/post/"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use eyre::WrapErr;
+use liquid::model::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::feeds::{rfc3339, xml_escape};
+use crate::i18n::{language_config, Alternate};
+use crate::sort::as_date_time;
+use crate::urls::absolute_url;
+use crate::{public_dir, write_to_location, Config, DocumentData};
+
+/// MAX_URLS is the most urls a sitemap file may list. Bigger sites get a sitemap index pointing
+/// at several sitemaps.
+const MAX_URLS: usize = 50_000;
+
+fn default_user_agent() -> String {
+    "*".to_string()
+}
+
+/// RobotsRule is a group of rules for the crawlers matching a user agent.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RobotsRule {
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+/// RobotsConfig is the `robots:` config section, which writes `robots.txt`, eg
+///
+/// ``` yaml
+/// robots:
+///   rules:
+///     - user_agent: "*"
+///       disallow: [/drafts/]
+///     - user_agent: GPTBot
+///       disallow: [/]
+/// ```
+///
+/// Without rules every crawler is allowed everywhere.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RobotsConfig {
+    pub enabled: bool,
+    pub rules: Vec<RobotsRule>,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        RobotsConfig {
+            enabled: true,
+            rules: vec![],
+        }
+    }
+}
+
+struct SitemapUrl {
+    loc: String,
+    lastmod: Option<String>,
+    alternates: Vec<Alternate>,
+}
+
+/// excluded is true for documents with `sitemap: false` or `noindex: true` in their frontmatter.
+fn excluded(document: &DocumentData) -> bool {
+    document.frontmatter.get("sitemap") == Some(&Value::Bool(false))
+        || document.frontmatter.get("noindex") == Some(&Value::Bool(true))
+}
+
+/// file_modified is when a file was last modified, in UTC, or None when there is no such file,
+/// eg for generated documents.
+pub(crate) fn file_modified(path: &str) -> Option<DateTime> {
+    let since_epoch = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?;
+    let mut modified = DateTime::from_ymd(1970, 1, 1);
+    *modified += since_epoch;
+    Some(modified)
+}
+
+/// lastmod is the `lastmod` or `updated` frontmatter, or else when the source file was last
+/// modified.
+fn lastmod(document: &DocumentData) -> eyre::Result<Option<String>> {
+    for key in ["lastmod", "updated"] {
+        if let Some(value) = document.frontmatter.get(key) {
+            let date = as_date_time(&liquid::model::to_value(value)?);
+            return Ok(date.map(|date| rfc3339(&date)));
+        }
+    }
+    Ok(file_modified(&document.file_path).map(|modified| rfc3339(&modified)))
+}
+
+fn collect_urls(config: &Config, documents: &[DocumentData]) -> eyre::Result<Vec<SitemapUrl>> {
+    let mut seen = HashSet::new();
+    let mut urls = vec![];
+    for document in documents {
+        if !document.file_destination_path.ends_with(".html")
+            || document.permalink == "/404.html"
+            || excluded(document)
+            || !seen.insert(document.file_destination_path.as_str())
+        {
+            continue;
+        }
+        urls.push(SitemapUrl {
+            loc: absolute_url(
+                &language_config(config, &document.lang).base_url,
+                &document.permalink,
+            ),
+            lastmod: lastmod(document)
+                .wrap_err(format!("invalid `lastmod` in {}", document.file_path))?,
+            alternates: document.hreflang.clone(),
+        });
+    }
+    // Keep the sitemap stable between builds, whatever order the files were read in.
+    urls.sort_by(|a, b| a.loc.cmp(&b.loc));
+    Ok(urls)
+}
+
+fn urlset(urls: &[SitemapUrl]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n");
+    for url in urls {
+        xml.push_str("<url>\n");
+        xml.push_str(&format!("<loc>{}</loc>\n", xml_escape(&url.loc)));
+        if let Some(lastmod) = &url.lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>\n", lastmod));
+        }
+        for alternate in &url.alternates {
+            xml.push_str(&format!(
+                "<xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\"/>\n",
+                xml_escape(&alternate.hreflang),
+                xml_escape(&alternate.href)
+            ));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+fn sitemap_index(sitemaps: &[String]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for sitemap in sitemaps {
+        xml.push_str(&format!(
+            "<sitemap><loc>{}</loc></sitemap>\n",
+            xml_escape(sitemap)
+        ));
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+fn robots_txt(config: &Config) -> String {
+    let mut txt = String::new();
+    if config.robots.rules.is_empty() {
+        txt.push_str("User-agent: *\nAllow: /\n");
+    }
+    for (i, rule) in config.robots.rules.iter().enumerate() {
+        if i > 0 {
+            txt.push('\n');
+        }
+        txt.push_str(&format!("User-agent: {}\n", rule.user_agent));
+        for path in &rule.allow {
+            txt.push_str(&format!("Allow: {}\n", path));
+        }
+        for path in &rule.disallow {
+            txt.push_str(&format!("Disallow: {}\n", path));
+        }
+    }
+    if config.sitemap {
+        txt.push_str(&format!(
+            "\nSitemap: {}\n",
+            absolute_url(&config.base_url, "/sitemap.xml")
+        ));
+    }
+    txt
+}
+
+/// write_sitemap writes `sitemap.xml`, listing every rendered html page, and `robots.txt`. Sites
+/// which provide either file themselves keep their own.
+pub fn write_sitemap(
+    root_dir: &str,
+    config: &Config,
+    documents: &[DocumentData],
+) -> eyre::Result<()> {
+    let public = public_dir(root_dir);
+    let provided = |name: &str| {
+        Path::new(root_dir).join(name).exists()
+            || documents
+                .iter()
+                .any(|d| d.file_destination_path == format!("{}/{}", public, name))
+    };
+
+    if config.sitemap && !provided("sitemap.xml") {
+        let urls = collect_urls(config, documents)?;
+        if urls.len() <= MAX_URLS {
+            write_to_location(format!("{}/sitemap.xml", public), urlset(&urls).as_bytes())?;
+        } else {
+            let mut sitemaps = vec![];
+            for (i, chunk) in urls.chunks(MAX_URLS).enumerate() {
+                let name = format!("sitemap-{}.xml", i + 1);
+                write_to_location(format!("{}/{}", public, name), urlset(chunk).as_bytes())?;
+                sitemaps.push(absolute_url(&config.base_url, &format!("/{}", name)));
+            }
+            write_to_location(
+                format!("{}/sitemap.xml", public),
+                sitemap_index(&sitemaps).as_bytes(),
+            )?;
+        }
+    }
+
+    if config.robots.enabled && !provided("robots.txt") {
+        write_to_location(
+            format!("{}/robots.txt", public),
+            robots_txt(config).as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    #[test]
+    fn urls_are_absolute_and_dated_by_their_frontmatter() {
+        let config = test_config("base_url: https://example.org/docs/");
+        let documents = vec![
+            test_document(&config, "b.md", "/b/index.html", "lastmod: 2024-03-01"),
+            test_document(
+                &config,
+                "a.md",
+                "/a/index.html",
+                "updated: 2024-02-01 10:30:00 +0000",
+            ),
+            test_document(&config, "hidden.md", "/hidden/index.html", "sitemap: false"),
+            test_document(&config, "feed.liquid", "/feed.xml", "{}"),
+            test_document(&config, "404.md", "/404.html", "{}"),
+        ];
+        let urls = collect_urls(&config, &documents).unwrap();
+        let xml = urlset(&urls);
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n\
+             <url>\n<loc>https://example.org/docs/a/</loc>\n<lastmod>2024-02-01T10:30:00+00:00</lastmod>\n</url>\n\
+             <url>\n<loc>https://example.org/docs/b/</loc>\n<lastmod>2024-03-01T00:00:00+00:00</lastmod>\n</url>\n\
+             </urlset>\n"
+        );
+    }
+
+    #[test]
+    fn undated_urls_have_no_lastmod_without_a_source_file() {
+        let config = test_config("");
+        let documents = vec![test_document(&config, "a.md", "/a/index.html", "{}")];
+        let urls = collect_urls(&config, &documents).unwrap();
+        assert_eq!(urls[0].lastmod, None);
+    }
+
+    #[test]
+    fn robots_txt_allows_everything_without_rules() {
+        let config = test_config("sitemap: true");
+        assert_eq!(
+            robots_txt(&config),
+            "User-agent: *\nAllow: /\n\nSitemap: https://example.org/sitemap.xml\n"
+        );
+    }
+
+    #[test]
+    fn robots_txt_writes_a_group_per_rule() {
+        let config = test_config(
+            "sitemap: false\nrobots: {rules: [{disallow: [/drafts/], allow: [/drafts/public/]}, {user_agent: GPTBot, disallow: [/]}]}",
+        );
+        assert_eq!(
+            robots_txt(&config),
+            "User-agent: *\nAllow: /drafts/public/\nDisallow: /drafts/\n\nUser-agent: GPTBot\nDisallow: /\n"
+        );
+    }
+}
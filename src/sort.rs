@@ -4,34 +4,212 @@ use liquid_core::Runtime;
 use liquid_core::{
     Display_filter, Filter, FilterParameters, FilterReflection, FromFilterParameters, ParseFilter,
 };
+use liquid_core::model::DateTime;
 use liquid_core::{Value, ValueView};
 use std::cmp;
-use crate::where_glob::{invalid_input, as_sequence};
+use crate::where_glob::{as_sequence, invalid_argument, invalid_input};
 
+/// Collation decides how two strings are ordered by the sort filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Collation {
+    /// Byte-wise and case-sensitive, as liquid compares strings.
+    Default,
+    /// Case-insensitive.
+    NoCase,
+    /// Case-insensitive, with runs of digits compared by their numeric value, so `page 2`
+    /// sorts before `page 10`.
+    Natural,
+}
+
+fn parse_collation(collate: &str) -> Result<Collation> {
+    match collate {
+        "" | "default" => Ok(Collation::Default),
+        "nocase" => Ok(Collation::NoCase),
+        "natural" => Ok(Collation::Natural),
+        _ => Err(invalid_argument(
+            "collate",
+            format!("unknown collation `{}`, expected default, nocase or natural", collate),
+        )),
+    }
+}
+
+/// SortKey is one property in a multi-key sort, eg `frontmatter.date desc`.
+#[derive(Debug, Clone, PartialEq)]
+struct SortKey {
+    property: String,
+    descending: bool,
+}
+
+/// parse_sort_keys reads a comma separated list of properties, each optionally followed by `asc`
+/// or `desc`. A leading `-` is shorthand for `desc`.
+fn parse_sort_keys(spec: &str) -> Result<Vec<SortKey>> {
+    let mut keys = vec![];
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let mut words = part.split_whitespace();
+        let property = words.next().unwrap_or_default();
+        let (property, mut descending) = match property.strip_prefix('-') {
+            Some(property) => (property, true),
+            None => (property, false),
+        };
+        match words.next() {
+            None | Some("asc") => {}
+            Some("desc") => descending = true,
+            Some(direction) => {
+                return Err(invalid_argument(
+                    "property",
+                    format!("unknown sort direction `{}` for `{}`", direction, property),
+                ))
+            }
+        }
+        keys.push(SortKey {
+            property: property.to_string(),
+            descending,
+        });
+    }
+    Ok(keys)
+}
+
+/// as_date_time interprets dates and date strings such as `2023-05-01` so they are ordered
+/// chronologically rather than as text. Numbers are never treated as dates.
+pub(crate) fn as_date_time(value: &Value) -> Option<DateTime> {
+    let scalar = value.as_scalar()?;
+    if scalar.to_float().is_some() || scalar.to_bool().is_some() {
+        return None;
+    }
+    if let "now" | "today" = scalar.to_kstr().trim().to_lowercase().as_str() {
+        return None;
+    }
+    scalar
+        .to_date_time()
+        .or_else(|| scalar.to_date().map(|d| DateTime::from_ymd(d.year(), d.month(), d.day())))
+}
+
+/// natural_cmp compares strings case-insensitively, treating runs of digits as numbers.
+fn natural_cmp(a: &str, b: &str) -> cmp::Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return cmp::Ordering::Equal,
+            (None, Some(_)) => return cmp::Ordering::Less,
+            (Some(_), None) => return cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_digits = String::new();
+                while let Some(c) = a_chars.next_if(|c| c.is_ascii_digit()) {
+                    x_digits.push(c);
+                }
+                let mut y_digits = String::new();
+                while let Some(c) = b_chars.next_if(|c| c.is_ascii_digit()) {
+                    y_digits.push(c);
+                }
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ordering != cmp::Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != cmp::Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
 
-fn nil_safe_compare(a: Value, b: Value) -> Option<cmp::Ordering> {
+fn compare_values(a: &Value, b: &Value, collation: Collation) -> Option<cmp::Ordering> {
+    if let (Some(a_date), Some(b_date)) = (as_date_time(a), as_date_time(b)) {
+        return a_date.partial_cmp(&b_date);
+    }
+    if collation != Collation::Default {
+        if let (Some(a_scalar), Some(b_scalar)) = (a.as_scalar(), b.as_scalar()) {
+            if a_scalar.to_float().is_none() && b_scalar.to_float().is_none() {
+                let (a_str, b_str) = (a_scalar.to_kstr(), b_scalar.to_kstr());
+                return Some(match collation {
+                    Collation::Natural => natural_cmp(a_str.as_str(), b_str.as_str()),
+                    _ => a_str.to_lowercase().cmp(&b_str.to_lowercase()),
+                });
+            }
+        }
+    }
+    a.partial_cmp(b)
+}
+
+/// nil_safe_compare orders nil values after everything else, whatever the sort direction.
+fn nil_safe_compare(
+    a: &Value,
+    b: &Value,
+    collation: Collation,
+    descending: bool,
+) -> Option<cmp::Ordering> {
     if a.is_nil() && b.is_nil() {
         Some(cmp::Ordering::Equal)
     } else if a.is_nil() {
         Some(cmp::Ordering::Greater)
     } else if b.is_nil() {
         Some(cmp::Ordering::Less)
+    } else if descending {
+        compare_values(a, b, collation).map(cmp::Ordering::reverse)
     } else {
-        a.partial_cmp(&b)
+        compare_values(a, b, collation)
     }
 }
 
+fn compare_by_keys(a: &Value, b: &Value, keys: &[SortKey], collation: Collation) -> cmp::Ordering {
+    keys.iter()
+        .map(|key| {
+            nil_safe_compare(
+                &safe_property_getter(a, &key.property),
+                &safe_property_getter(b, &key.property),
+                collation,
+                key.descending,
+            )
+            .unwrap_or(cmp::Ordering::Equal)
+        })
+        .find(|ordering| *ordering != cmp::Ordering::Equal)
+        .unwrap_or(cmp::Ordering::Equal)
+}
+
+/// sort_by_spec orders items by a sort spec such as `frontmatter.date desc`, the same way the
+/// sort filter does. Each item is paired with the object its properties are read from.
+pub(crate) fn sort_by_spec<T>(items: Vec<(Value, T)>, spec: &str) -> Result<Vec<T>> {
+    let keys = parse_sort_keys(spec)?;
+    let mut items = items;
+    items.sort_by(|(a, _), (b, _)| compare_by_keys(a, b, &keys, Collation::Default));
+    Ok(items.into_iter().map(|(_, item)| item).collect())
+}
+
 #[derive(Debug, Default, FilterParameters)]
-struct PropertyArgs {
-    #[parameter(description = "The property accessed by the filter.", arg_type = "str")]
+struct SortArgs {
+    #[parameter(
+        description = "The properties accessed by the filter, separated by commas. Each may be \
+                       followed by `asc` or `desc`, eg `frontmatter.weight, frontmatter.date desc`.",
+        arg_type = "str"
+    )]
     property: Option<Expression>,
+    #[parameter(
+        description = "How strings are compared: `default` (case-sensitive), `nocase` or `natural`.",
+        arg_type = "str",
+        mode = "keyword"
+    )]
+    collate: Option<Expression>,
 }
 
 #[derive(Clone, ParseFilter, FilterReflection)]
 #[filter(
     name = "sort",
-    description = "Sorts items in an array. The order of the sorted array is case-sensitive.",
-    parameters(PropertyArgs),
+    description = "Sorts items in an array by one or more properties. Date strings are ordered \
+                   chronologically and nil values always come last. The order of strings is \
+                   case-sensitive unless another collation is given.",
+    parameters(SortArgs),
     parsed(SortFilter)
 )]
 pub struct Sort;
@@ -40,10 +218,10 @@ pub struct Sort;
 #[name = "sort"]
 struct SortFilter {
     #[parameters]
-    args: PropertyArgs,
+    args: SortArgs,
 }
 
-fn safe_property_getter<'a>(value: &'a Value, property: &str) ->Value {
+pub(crate) fn safe_property_getter(value: &Value, property: &str) -> Value {
     let mut current_value = value.to_value();
     
     for key in property.split('.') {
@@ -64,25 +242,70 @@ fn safe_property_getter<'a>(value: &'a Value, property: &str) ->Value {
 impl Filter for SortFilter {
     fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
         let args = self.args.evaluate(runtime)?;
+        let collation = parse_collation(args.collate.as_deref().unwrap_or("default"))?;
+        let keys = match &args.property {
+            Some(property) => parse_sort_keys(property)?,
+            None => vec![],
+        };
 
         let input: Vec<_> = as_sequence(input).collect();
-        if args.property.is_some() && !input.iter().all(|v| v.is_object()) {
+        if !keys.is_empty() && !input.iter().all(|v| v.is_object()) {
             return Err(invalid_input("Array of objects expected"));
         }
 
         let mut sorted: Vec<Value> = input.iter().map(|v| v.to_value()).collect();
-        if let Some(property) = &args.property {
-            // Using unwrap is ok since all of the elements are objects
+        if keys.is_empty() {
             sorted.sort_by(|a, b| {
-                nil_safe_compare(
-                    safe_property_getter(a, property),
-                    safe_property_getter(b, property),
-                )
-                .unwrap_or(cmp::Ordering::Equal)
+                nil_safe_compare(a, b, collation, false).unwrap_or(cmp::Ordering::Equal)
             });
         } else {
-            sorted.sort_by(|a, b| nil_safe_compare(a.clone(), b.clone()).unwrap_or(cmp::Ordering::Equal));
+            sorted.sort_by(|a, b| compare_by_keys(a, b, &keys, collation));
         }
         Ok(Value::array(sorted))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(property: &str, descending: bool) -> SortKey {
+        SortKey {
+            property: property.to_string(),
+            descending,
+        }
+    }
+
+    #[test]
+    fn parse_sort_keys_reads_directions() {
+        assert_eq!(
+            parse_sort_keys("frontmatter.date desc, title").unwrap(),
+            vec![key("frontmatter.date", true), key("title", false)]
+        );
+        assert_eq!(
+            parse_sort_keys("-weight,title asc").unwrap(),
+            vec![key("weight", true), key("title", false)]
+        );
+        assert_eq!(parse_sort_keys(" , ").unwrap(), vec![]);
+        assert!(parse_sort_keys("title sideways").is_err());
+    }
+
+    #[test]
+    fn natural_cmp_orders_numbers_by_value() {
+        assert_eq!(natural_cmp("page 2", "page 10"), cmp::Ordering::Less);
+        assert_eq!(natural_cmp("Page 2", "page 2"), cmp::Ordering::Equal);
+        assert_eq!(natural_cmp("a10", "a9"), cmp::Ordering::Greater);
+        assert_eq!(natural_cmp("b", "A"), cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn as_date_time_reads_dates_but_not_numbers() {
+        let date = as_date_time(&Value::scalar("2023-05-01")).unwrap();
+        assert_eq!((date.year(), date.month(), date.day()), (2023, 5, 1));
+        assert!(as_date_time(&Value::scalar("2023-05-01 10:30:00 +0000")).is_some());
+        assert!(as_date_time(&Value::scalar(2023)).is_none());
+        assert!(as_date_time(&Value::scalar("today")).is_none());
+        assert!(as_date_time(&Value::scalar("hello")).is_none());
+        assert!(as_date_time(&Value::Nil).is_none());
+    }
+}
@@ -0,0 +1,273 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::i18n::language_prefix;
+use crate::{Config, DocumentData};
+
+/// TaxonomyConfig is one entry of the `taxonomies:` config section, eg
+///
+/// ``` yaml
+/// taxonomies:
+///   tags:
+///     list_layout: default/tags.liquid
+///     term_layout: default/tag.liquid
+///   categories:
+///     key: category
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct TaxonomyConfig {
+    /// key is the frontmatter key terms are read from. It defaults to the taxonomy name.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// path is the url the taxonomy pages are generated under. It defaults to the taxonomy name.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// list_layout renders the page listing every term, eg `/tags/`.
+    #[serde(default)]
+    pub list_layout: Option<String>,
+    /// term_layout renders one page per term, eg `/tags/rust/`.
+    #[serde(default)]
+    pub term_layout: Option<String>,
+}
+
+/// Term is a single value of a taxonomy, and the documents which use it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Term {
+    pub name: String,
+    pub slug: String,
+    pub permalink: String,
+    pub pages: Vec<DocumentData>,
+}
+
+/// Taxonomies maps each taxonomy name to its terms, keyed by slug.
+pub type Taxonomies = IndexMap<String, IndexMap<String, Term>>;
+
+/// LanguageTaxonomies holds the taxonomies of every language, keyed by language. Pages only
+/// share terms with pages in their own language, and each language has its own term pages, eg
+/// `/fr/tags/rust/`.
+pub type LanguageTaxonomies = IndexMap<String, Taxonomies>;
+
+/// slugify lowercases a term and replaces everything but letters and digits with dashes.
+pub fn slugify(term: &str) -> String {
+    let mut slug = String::with_capacity(term.len());
+    for c in term.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+fn taxonomy_path(name: &str, taxonomy: &TaxonomyConfig) -> String {
+    taxonomy
+        .path
+        .as_deref()
+        .unwrap_or(name)
+        .trim_matches('/')
+        .to_string()
+}
+
+/// frontmatter_terms reads the terms of a document, which may be a single value or a list.
+fn frontmatter_terms(frontmatter: &Value, key: &str) -> Vec<String> {
+    let scalar_to_string = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    match frontmatter.get(key) {
+        Some(Value::Sequence(values)) => values.iter().filter_map(scalar_to_string).collect(),
+        Some(value) => scalar_to_string(value).into_iter().collect(),
+        None => vec![],
+    }
+}
+
+/// languages lists the languages of the site, starting with the default language.
+fn languages(config: &Config) -> Vec<&String> {
+    let mut languages = vec![&config.default_language];
+    languages.extend(
+        config
+            .languages
+            .keys()
+            .filter(|lang| **lang != config.default_language),
+    );
+    languages
+}
+
+/// collect_taxonomies groups the documents of every language by the terms in their frontmatter,
+/// for every configured taxonomy. Terms are ordered by name.
+pub fn collect_taxonomies(config: &Config, documents: &[DocumentData]) -> LanguageTaxonomies {
+    let mut by_language = LanguageTaxonomies::new();
+    for lang in languages(config) {
+        let prefix = language_prefix(config, lang);
+        let mut taxonomies = Taxonomies::new();
+        for (name, taxonomy) in &config.taxonomies {
+            let key = taxonomy.key.as_deref().unwrap_or(name);
+            let path = taxonomy_path(name, taxonomy);
+            let mut terms: IndexMap<String, Term> = IndexMap::new();
+            for document in documents.iter().filter(|d| d.lang == *lang) {
+                for term_name in frontmatter_terms(&document.frontmatter, key) {
+                    let slug = slugify(&term_name);
+                    if slug.is_empty() {
+                        continue;
+                    }
+                    let term = terms.entry(slug.clone()).or_insert_with(|| Term {
+                        name: term_name.clone(),
+                        permalink: format!("{}/{}/{}/", prefix, path, slug),
+                        slug,
+                        pages: vec![],
+                    });
+                    if !term.pages.iter().any(|p| p.file_path == document.file_path) {
+                        term.pages.push(document.clone());
+                    }
+                }
+            }
+            terms.sort_by(|_, a, _, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+            taxonomies.insert(name.clone(), terms);
+        }
+        by_language.insert(lang.clone(), taxonomies);
+    }
+    by_language
+}
+
+/// generated_page creates a taxonomy page in a language. The destination is the one of the
+/// default language, eg `tags/index.html`, which other languages get under their prefix.
+fn generated_page(
+    root_dir: &str,
+    config: &Config,
+    lang: &str,
+    destination: String,
+    layout: &str,
+    fields: Vec<(&str, String)>,
+    data: Value,
+) -> DocumentData {
+    let mut frontmatter = Mapping::new();
+    frontmatter.insert("layout".into(), layout.into());
+    for (key, value) in fields {
+        frontmatter.insert(key.into(), value.into());
+    }
+    let source_path = format!("{}.md", destination.trim_end_matches(".html"));
+    let prefix = language_prefix(config, lang);
+    let mut page = DocumentData::new(
+        root_dir,
+        format!("{}{}/{}", root_dir, prefix, source_path),
+        &format!("{}/{}", prefix, destination),
+        String::new(),
+        Value::Mapping(frontmatter),
+        0,
+    );
+    page.data = data;
+    page.lang = lang.to_string();
+    page.translation_key = source_path;
+    page
+}
+
+/// taxonomy_pages creates the term list and term pages of every language for every taxonomy
+/// with layouts configured. The term list page gets the terms as `this.data`, ordered by name,
+/// and each term page gets its term. Pages which already exist in the site are not generated.
+pub fn taxonomy_pages(
+    root_dir: &str,
+    config: &Config,
+    taxonomies: &LanguageTaxonomies,
+    documents: &[DocumentData],
+) -> eyre::Result<Vec<DocumentData>> {
+    let exists = |permalink: &str| documents.iter().any(|d| d.permalink == permalink);
+    let mut pages = vec![];
+    for (lang, language_taxonomies) in taxonomies {
+        for (name, taxonomy) in &config.taxonomies {
+            let path = taxonomy_path(name, taxonomy);
+            let terms: Vec<&Term> = language_taxonomies
+                .get(name)
+                .map(|terms| terms.values().collect())
+                .unwrap_or_default();
+            if let Some(layout) = &taxonomy.list_layout {
+                let page = generated_page(
+                    root_dir,
+                    config,
+                    lang,
+                    format!("{}/index.html", path),
+                    layout,
+                    vec![("title", name.clone()), ("taxonomy", name.clone())],
+                    serde_yaml::to_value(&terms)?,
+                );
+                if !exists(&page.permalink) {
+                    pages.push(page);
+                }
+            }
+            if let Some(layout) = &taxonomy.term_layout {
+                for term in terms {
+                    if exists(&term.permalink) {
+                        continue;
+                    }
+                    pages.push(generated_page(
+                        root_dir,
+                        config,
+                        lang,
+                        format!("{}/{}/index.html", path, term.slug),
+                        layout,
+                        vec![
+                            ("title", term.name.clone()),
+                            ("taxonomy", name.clone()),
+                            ("term", term.slug.clone()),
+                        ],
+                        serde_yaml::to_value(term)?,
+                    ));
+                }
+            }
+        }
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_config, test_document};
+
+    #[test]
+    fn slugify_replaces_everything_but_letters_and_digits() {
+        assert_eq!(slugify("Rust & Web"), "rust-web");
+        assert_eq!(slugify("  Été 2024 "), "été-2024");
+        assert_eq!(slugify("--"), "");
+    }
+
+    #[test]
+    fn terms_are_collected_per_language() {
+        let config =
+            test_config("languages:\n  fr: {}\ntaxonomies:\n  tags:\n    term_layout: tag.liquid");
+        let documents = [
+            ("a.md", "a.html", "tags: [Rust, web]"),
+            ("fr/a.md", "fr/a.html", "tags: rust"),
+        ]
+        .map(|(source, destination, frontmatter)| {
+            test_document(&config, source, destination, frontmatter)
+        });
+        let taxonomies = collect_taxonomies(&config, &documents);
+
+        let en = &taxonomies["en"]["tags"];
+        assert_eq!(en.keys().collect::<Vec<_>>(), ["rust", "web"]);
+        assert_eq!(en["rust"].pages.len(), 1);
+        assert_eq!(en["rust"].pages[0].file_path, "/site/a.md");
+        let fr = &taxonomies["fr"]["tags"];
+        assert_eq!(fr.keys().collect::<Vec<_>>(), ["rust"]);
+        assert_eq!(fr["rust"].permalink, "/fr/tags/rust/");
+        assert_eq!(fr["rust"].pages[0].file_path, "/site/fr/a.md");
+
+        let pages = taxonomy_pages("/site", &config, &taxonomies, &documents).unwrap();
+        let pages: Vec<(&str, &str)> = pages
+            .iter()
+            .map(|page| (page.permalink.as_str(), page.lang.as_str()))
+            .collect();
+        assert_eq!(
+            pages,
+            [
+                ("/tags/rust/", "en"),
+                ("/tags/web/", "en"),
+                ("/fr/tags/rust/", "fr")
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use liquid_core::parser::FilterArguments;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::{Display_filter, Filter, FilterReflection, ParseFilter};
+use liquid_core::{Value, ValueView};
+
+/// base_path_from_url extracts the path a site is deployed under from its base_url.
+/// Eg `https://example.org/docs/` becomes `/docs`, while `https://example.org` becomes an empty
+/// string, so it can always be prepended to root-relative urls.
+pub fn base_path_from_url(base_url: &str) -> String {
+    let path = match base_url.split_once("://") {
+        Some((_, after_scheme)) => after_scheme.find('/').map_or("", |i| &after_scheme[i..]),
+        None => base_url,
+    };
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+fn is_absolute_url(url: &str) -> bool {
+    url.starts_with("//") || url.contains("://") || url.starts_with("mailto:")
+}
+
+/// relative_url prefixes a site url with the base_path. Urls which are already absolute are
+/// returned unchanged.
+pub fn relative_url(base_path: &str, url: &str) -> String {
+    if is_absolute_url(url) {
+        return url.to_string();
+    }
+    format!("{}/{}", base_path, url.trim_start_matches('/'))
+}
+
+/// absolute_url turns a site url into a full url under the configured base_url.
+pub fn absolute_url(base_url: &str, url: &str) -> String {
+    if is_absolute_url(url) {
+        return url.to_string();
+    }
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        url.trim_start_matches('/')
+    )
+}
+
+const URL_ATTRIBUTES: [&str; 4] = ["href=\"/", "href='/", "src=\"/", "src='/"];
+
+/// prefix_root_relative_urls rewrites root-relative `href` and `src` attributes in rendered html
+/// so they point under the base_path. Protocol-relative urls, and urls which already start with
+/// the base_path, are left as they are.
+pub fn prefix_root_relative_urls(html: &str, base_path: &str) -> String {
+    if base_path.is_empty() {
+        return html.to_string();
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some((index, len)) = URL_ATTRIBUTES
+        .iter()
+        .filter_map(|attr| rest.find(attr).map(|i| (i, attr.len())))
+        .min()
+    {
+        // Split just before the leading slash of the url.
+        let split_at = index + len - 1;
+        output.push_str(&rest[..split_at]);
+        rest = &rest[split_at..];
+
+        let already_prefixed = rest
+            .strip_prefix(base_path)
+            .is_some_and(|after| after.starts_with(['/', '"', '\'', '?', '#']));
+        if !rest.starts_with("//") && !already_prefixed {
+            output.push_str(base_path);
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// absolute_root_relative_urls rewrites root-relative `href` and `src` attributes in rendered
+/// html into full urls under the base_url, for html read outside the site, eg in feeds.
+pub fn absolute_root_relative_urls(html: &str, base_url: &str) -> String {
+    let base_path = base_path_from_url(base_url);
+    let origin = base_url.trim_end_matches('/');
+    let origin = origin.strip_suffix(&base_path).unwrap_or(origin);
+    // The origin never starts with a slash, so it is prepended to every root-relative url.
+    prefix_root_relative_urls(&prefix_root_relative_urls(html, &base_path), origin)
+}
+
+pub(crate) fn input_as_string(input: &dyn ValueView) -> Result<String> {
+    Ok(input
+        .to_value()
+        .as_scalar()
+        .ok_or_else(|| liquid_core::Error::with_msg("Input is not a scalar value"))?
+        .to_kstr()
+        .into_string())
+}
+
+pub(crate) fn expect_no_arguments(mut args: FilterArguments) -> Result<()> {
+    if args.positional.next().is_some() {
+        return Err(liquid_core::Error::with_msg("Invalid number of positional arguments")
+            .context("cause", "expected at most 0 positional arguments"));
+    }
+    if let Some(arg) = args.keyword.next() {
+        return Err(liquid_core::Error::with_msg(format!(
+            "Unexpected named argument `{}`",
+            arg.0
+        )));
+    }
+    Ok(())
+}
+
+// AbsoluteUrl Filter
+//
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "absolute_url",
+    description = "Prefixes a site url with the configured base_url.",
+    parsed(AbsoluteUrlFilter)
+)]
+pub struct AbsoluteUrl {
+    pub base_url: String,
+}
+
+impl ParseFilter for AbsoluteUrl {
+    fn parse(&self, args: FilterArguments) -> Result<Box<dyn Filter>> {
+        expect_no_arguments(args)?;
+        Ok(Box::new(AbsoluteUrlFilter {
+            base_url: self.base_url.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "absolute_url"]
+struct AbsoluteUrlFilter {
+    base_url: String,
+}
+
+impl Filter for AbsoluteUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        let url = input_as_string(input)?;
+        Ok(Value::scalar(absolute_url(&self.base_url, &url)))
+    }
+}
+
+// RelativeUrl Filter
+//
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "relative_url",
+    description = "Prefixes a site url with the path of the configured base_url.",
+    parsed(RelativeUrlFilter)
+)]
+pub struct RelativeUrl {
+    pub base_path: String,
+}
+
+impl ParseFilter for RelativeUrl {
+    fn parse(&self, args: FilterArguments) -> Result<Box<dyn Filter>> {
+        expect_no_arguments(args)?;
+        Ok(Box::new(RelativeUrlFilter {
+            base_path: self.base_path.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "relative_url"]
+struct RelativeUrlFilter {
+    base_path: String,
+}
+
+impl Filter for RelativeUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        let url = input_as_string(input)?;
+        Ok(Value::scalar(relative_url(&self.base_path, &url)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_path_from_url_keeps_the_path() {
+        assert_eq!(base_path_from_url("https://example.org/docs/"), "/docs");
+        assert_eq!(base_path_from_url("https://example.org/a/b"), "/a/b");
+        assert_eq!(base_path_from_url("https://example.org"), "");
+        assert_eq!(base_path_from_url("https://example.org/"), "");
+        assert_eq!(base_path_from_url("/docs/"), "/docs");
+    }
+
+    #[test]
+    fn relative_url_prefixes_the_base_path() {
+        assert_eq!(relative_url("/docs", "/blog/"), "/docs/blog/");
+        assert_eq!(relative_url("/docs", "blog/"), "/docs/blog/");
+        assert_eq!(relative_url("", "/blog/"), "/blog/");
+        assert_eq!(
+            relative_url("/docs", "https://other.org/x"),
+            "https://other.org/x"
+        );
+        assert_eq!(relative_url("/docs", "//cdn.org/x.js"), "//cdn.org/x.js");
+    }
+
+    #[test]
+    fn absolute_url_joins_the_base_url() {
+        assert_eq!(
+            absolute_url("https://example.org/docs/", "/blog/"),
+            "https://example.org/docs/blog/"
+        );
+        assert_eq!(
+            absolute_url("https://example.org", "blog/"),
+            "https://example.org/blog/"
+        );
+        assert_eq!(
+            absolute_url("https://example.org", "mailto:a@b.org"),
+            "mailto:a@b.org"
+        );
+    }
+
+    #[test]
+    fn prefix_root_relative_urls_rewrites_href_and_src() {
+        let html = r#"<a href="/blog/">b</a><img src='/a.png'><a href="x/">x</a>"#;
+        assert_eq!(
+            prefix_root_relative_urls(html, "/docs"),
+            r#"<a href="/docs/blog/">b</a><img src='/docs/a.png'><a href="x/">x</a>"#
+        );
+    }
+
+    #[test]
+    fn prefix_root_relative_urls_skips_prefixed_and_protocol_relative() {
+        let html = r#"<a href="/docs/blog/"></a><a href="/docs"></a><script src="//cdn.org/x.js"></script><a href="/docsearch/"></a>"#;
+        assert_eq!(
+            prefix_root_relative_urls(html, "/docs"),
+            r#"<a href="/docs/blog/"></a><a href="/docs"></a><script src="//cdn.org/x.js"></script><a href="/docs/docsearch/"></a>"#
+        );
+        assert_eq!(prefix_root_relative_urls(html, ""), html);
+    }
+
+    #[test]
+    fn absolute_root_relative_urls_adds_the_base_url() {
+        let html =
+            r#"<a href="/blog/">b</a><img src="/docs/a.png"><a href="https://other.org/">o</a>"#;
+        assert_eq!(
+            absolute_root_relative_urls(html, "https://example.org/docs/"),
+            r#"<a href="https://example.org/docs/blog/">b</a><img src="https://example.org/docs/a.png"><a href="https://other.org/">o</a>"#
+        );
+        assert_eq!(
+            absolute_root_relative_urls(r#"<a href="/blog/">b</a>"#, "https://example.org/"),
+            r#"<a href="https://example.org/blog/">b</a>"#
+        );
+    }
+}
@@ -5,7 +5,9 @@ use liquid_core::Runtime;
 use liquid_core::{
     Display_filter, Filter, FilterParameters, FilterReflection, FromFilterParameters, ParseFilter,
 };
-use liquid_core::{Value, ValueCow, ValueView};
+use liquid_core::{Value, ValueView};
+
+use crate::sort::safe_property_getter;
 
 #[derive(Debug, FilterParameters)]
 struct WhereGlobArgs {
@@ -16,12 +18,22 @@ struct WhereGlobArgs {
         arg_type = "any"
     )]
     target_value: Option<Expression>,
+    #[parameter(
+        description = "How the property is compared with the target value. One of `glob` \
+                       (the default), `contains`, `>`, `>=`, `<` or `<=`. Prefix with `!` to \
+                       negate, eg `!=` or `!contains`.",
+        arg_type = "str",
+        mode = "keyword"
+    )]
+    op: Option<Expression>,
 }
 
 #[derive(Clone, ParseFilter, FilterReflection)]
 #[filter(
     name = "where_glob",
     description = "Filter the elements of an array to those with a certain property value. \
+                   The property may be a dotted path such as `frontmatter.tags`, and array \
+                   properties match when any of their items match. \
                    By default the target is any truthy value.",
     parameters(WhereGlobArgs),
     parsed(WhereGlobFilter)
@@ -47,11 +59,97 @@ pub fn as_sequence<'k>(
     }
 }
 
+/// WhereOp is the comparison applied between a document property and the `where_glob` target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WhereOp {
+    Glob,
+    Contains,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// parse_where_op splits an operator such as `!contains` into the operator and whether it is
+/// negated.
+fn parse_where_op(op: &str) -> Result<(WhereOp, bool)> {
+    let (negate, op) = match op.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, op),
+    };
+    let where_op = match op {
+        "" | "=" | "==" | "glob" => WhereOp::Glob,
+        "contains" => WhereOp::Contains,
+        ">" => WhereOp::Gt,
+        ">=" => WhereOp::Ge,
+        "<" => WhereOp::Lt,
+        "<=" => WhereOp::Le,
+        _ => {
+            return Err(invalid_argument(
+                "op",
+                format!("unknown where_glob operator `{}`", op),
+            ))
+        }
+    };
+    Ok((where_op, negate))
+}
+
+fn glob_matches_scalar(pattern: &str, value: &dyn ValueView) -> bool {
+    value
+        .as_scalar()
+        .map(|scalar| glob_match::glob_match(pattern, scalar.to_kstr().as_str()))
+        .unwrap_or(false)
+}
+
+/// glob_matches reports whether a value, or any item of an array value, matches the pattern, the
+/// same way `where_glob` does.
+pub(crate) fn glob_matches(value: &Value, pattern: &str) -> bool {
+    where_matches(value, WhereOp::Glob, &Value::scalar(pattern.to_string()))
+}
+
+fn where_matches(value: &Value, op: WhereOp, target: &Value) -> bool {
+    match op {
+        WhereOp::Glob => {
+            let pattern = target.to_kstr();
+            match value.as_array() {
+                Some(array) => array
+                    .values()
+                    .any(|item| glob_matches_scalar(pattern.as_str(), item)),
+                None => glob_matches_scalar(pattern.as_str(), value),
+            }
+        }
+        WhereOp::Contains => {
+            if let Some(array) = value.as_array() {
+                array.values().any(|item| item.to_value() == *target)
+            } else if let Some(scalar) = value.as_scalar() {
+                scalar.to_kstr().contains(target.to_kstr().as_str())
+            } else {
+                false
+            }
+        }
+        WhereOp::Gt | WhereOp::Ge | WhereOp::Lt | WhereOp::Le => {
+            if value.is_nil() || target.is_nil() {
+                return false;
+            }
+            match value.partial_cmp(target) {
+                Some(ordering) => match op {
+                    WhereOp::Gt => ordering.is_gt(),
+                    WhereOp::Ge => ordering.is_ge(),
+                    WhereOp::Lt => ordering.is_lt(),
+                    _ => ordering.is_le(),
+                },
+                None => false,
+            }
+        }
+    }
+}
+
 impl Filter for WhereGlobFilter {
     fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
         let args = self.args.evaluate(runtime)?;
         let property: &str = &args.property;
-        let target_value: Option<ValueCow<'_>> = args.target_value;
+        let target_value: Option<Value> = args.target_value.map(|v| v.into_owned());
+        let (op, negate) = parse_where_op(args.op.as_deref().unwrap_or("glob"))?;
 
         if let Some(array) = input.as_array() {
             if !array.values().all(|v| v.is_object()) {
@@ -63,34 +161,24 @@ impl Filter for WhereGlobFilter {
             ));
         }
 
-        let input = as_sequence(input);
-        let array: Vec<_> = match target_value {
-            None => input
-                .filter_map(|v| v.as_object())
-                .filter(|object| {
-                    object
-                        .get(property)
-                        .map(|v| v.query_state(liquid_core::model::State::Truthy))
-                        .unwrap_or(false)
-                })
-                .map(|object| object.to_value())
-                .collect(),
-            Some(target_value) => input
-                .filter_map(|v| v.as_object())
-                .filter(|object| {
-                    object
-                        .get(property)
-                        .map(|value| {
-                            glob_match::glob_match(
-                                target_value.to_kstr().as_str(),
-                                &value.as_scalar().unwrap().to_kstr().to_string(),
-                            )
-                        })
-                        .unwrap_or(false)
-                })
-                .map(|object| object.to_value())
-                .collect(),
-        };
+        if target_value.is_none() && op != WhereOp::Glob {
+            return Err(invalid_argument(
+                "target_value",
+                "a target value is required for comparison operators",
+            ));
+        }
+
+        let array: Vec<_> = as_sequence(input)
+            .map(|v| v.to_value())
+            .filter(|object| {
+                let value = safe_property_getter(object, property);
+                let matched = match &target_value {
+                    None => value.query_state(liquid_core::model::State::Truthy),
+                    Some(target_value) => where_matches(&value, op, target_value),
+                };
+                matched != negate
+            })
+            .collect();
         Ok(Value::array(array))
     }
 }
@@ -102,6 +190,16 @@ where
     Error::with_msg("Invalid input").context("cause", cause)
 }
 
+pub(crate) fn invalid_argument<A, C>(argument: A, cause: C) -> Error
+where
+    A: Into<liquid_core::model::KString>,
+    C: Into<liquid_core::model::KString>,
+{
+    Error::with_msg("Invalid argument")
+        .context("argument", argument)
+        .context("cause", cause)
+}
+
 //
 //
 //
@@ -297,3 +395,79 @@ impl Filter for MarkdownifyFilter {
         Ok(Value::scalar(result))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_where_op_reads_negation() {
+        assert_eq!(parse_where_op("glob").unwrap(), (WhereOp::Glob, false));
+        assert_eq!(parse_where_op("").unwrap(), (WhereOp::Glob, false));
+        assert_eq!(parse_where_op("!=").unwrap(), (WhereOp::Glob, true));
+        assert_eq!(
+            parse_where_op("!contains").unwrap(),
+            (WhereOp::Contains, true)
+        );
+        assert_eq!(parse_where_op(">=").unwrap(), (WhereOp::Ge, false));
+        assert_eq!(parse_where_op("<").unwrap(), (WhereOp::Lt, false));
+        assert!(parse_where_op("~").is_err());
+    }
+
+    #[test]
+    fn where_matches_globs_scalars_and_arrays() {
+        let tags = liquid_core::value!(["rust", "web"]);
+        assert!(where_matches(&tags, WhereOp::Glob, &Value::scalar("ru*")));
+        assert!(!where_matches(&tags, WhereOp::Glob, &Value::scalar("go")));
+        let path = Value::scalar("blog/hello.md");
+        assert!(where_matches(
+            &path,
+            WhereOp::Glob,
+            &Value::scalar("blog/**")
+        ));
+        assert!(!where_matches(
+            &Value::Nil,
+            WhereOp::Glob,
+            &Value::scalar("*")
+        ));
+    }
+
+    #[test]
+    fn where_matches_contains() {
+        let tags = liquid_core::value!(["rust", "web"]);
+        assert!(where_matches(
+            &tags,
+            WhereOp::Contains,
+            &Value::scalar("web")
+        ));
+        assert!(!where_matches(
+            &tags,
+            WhereOp::Contains,
+            &Value::scalar("we")
+        ));
+        let title = Value::scalar("Hello world");
+        assert!(where_matches(
+            &title,
+            WhereOp::Contains,
+            &Value::scalar("lo w")
+        ));
+    }
+
+    #[test]
+    fn where_matches_compares() {
+        let weight = Value::scalar(3);
+        assert!(where_matches(&weight, WhereOp::Gt, &Value::scalar(2)));
+        assert!(where_matches(&weight, WhereOp::Ge, &Value::scalar(3)));
+        assert!(!where_matches(&weight, WhereOp::Lt, &Value::scalar(3)));
+        assert!(where_matches(&weight, WhereOp::Le, &Value::scalar(3)));
+        // Missing values are neither bigger nor smaller than anything.
+        assert!(!where_matches(&Value::Nil, WhereOp::Lt, &Value::scalar(3)));
+    }
+
+    #[test]
+    fn glob_matches_any_item() {
+        assert!(glob_matches(&liquid_core::value!(["a/b", "c/d"]), "c/*"));
+        assert!(!glob_matches(&liquid_core::value!(["a/b"]), "c/*"));
+        assert!(glob_matches(&Value::scalar("docs/intro.md"), "docs/*.md"));
+    }
+}